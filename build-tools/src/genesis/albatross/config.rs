@@ -1,6 +1,10 @@
+use std::collections::HashSet;
+
 use chrono::{DateTime, Utc};
+use failure::Fail;
+use hash::{Blake2bHash, Blake2bHasher, Hasher};
 use keys::Address;
-use serde::{Deserialize, Deserializer};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use serde::de::Error;
 use primitives::coin::Coin;
 use bls::bls12_381::{
@@ -10,10 +14,37 @@ use bls::bls12_381::{
 use bls::Encoding;
 use std::convert::TryFrom;
 
+use account::staking_contract::DEFAULT_MAX_VALIDATOR_SLOTS;
+
+#[derive(Clone, Debug, Fail)]
+pub enum GenesisConfigError {
+    #[fail(display = "Genesis config declares {} validators, which exceeds the max_validator_slots cap of {}.", num_validators, max_validator_slots)]
+    TooManyValidators {
+        num_validators: usize,
+        max_validator_slots: u32,
+    },
+    #[fail(display = "Genesis config declares account address {} more than once.", address)]
+    DuplicateAccountAddress {
+        address: Address,
+    },
+    #[fail(display = "Genesis config declares staker address {} more than once.", staker_address)]
+    DuplicateStakerAddress {
+        staker_address: Address,
+    },
+    #[fail(display = "Genesis config declares validator key {} more than once.", validator_key)]
+    DuplicateValidatorKey {
+        validator_key: String,
+    },
+    #[fail(display = "Genesis config declares a zero account or stake balance, which is not allowed.")]
+    ZeroBalance,
+    #[fail(display = "Genesis config's total balance overflows the maximum Coin value.")]
+    BalanceOverflow,
+}
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct GenesisConfig {
     #[serde(default)]
+    #[serde(serialize_with = "serialize_bls_secret_key_opt")]
     #[serde(deserialize_with = "deserialize_bls_secret_key_opt")]
     pub signing_key: Option<BlsSecretKey>,
 
@@ -26,40 +57,118 @@ pub struct GenesisConfig {
 
     #[serde(default)]
     pub accounts: Vec<GenesisAccount>,
+
+    /// Upper bound on the number of distinct validators the genesis staking contract may
+    /// contain. Must match the `max_validator_slots` the live `StakingContract` is constructed
+    /// with, so genesis and post-genesis state never disagree on the cap.
+    #[serde(default = "default_max_validator_slots")]
+    pub max_validator_slots: u32,
+}
+
+impl GenesisConfig {
+    /// Rejects a genesis config whose validator set exceeds `max_validator_slots`, that declares
+    /// any address/validator key more than once, that declares a zero balance, or whose total
+    /// balance overflows `Coin`.
+    pub fn validate(&self) -> Result<(), GenesisConfigError> {
+        let validator_keys: HashSet<_> = self.stakes.iter().map(|stake| stake.validator_key.compress()).collect();
+        if validator_keys.len() as u32 > self.max_validator_slots {
+            return Err(GenesisConfigError::TooManyValidators {
+                num_validators: validator_keys.len(),
+                max_validator_slots: self.max_validator_slots,
+            });
+        }
+
+        let mut seen_accounts = HashSet::new();
+        for account in &self.accounts {
+            if !seen_accounts.insert(&account.address) {
+                return Err(GenesisConfigError::DuplicateAccountAddress { address: account.address.clone() });
+            }
+        }
+
+        let mut seen_stakers = HashSet::new();
+        let mut seen_validator_keys = HashSet::new();
+        for stake in &self.stakes {
+            if !seen_stakers.insert(&stake.staker_address) {
+                return Err(GenesisConfigError::DuplicateStakerAddress { staker_address: stake.staker_address.clone() });
+            }
+
+            let compressed = stake.validator_key.compress();
+            if !seen_validator_keys.insert(compressed.clone()) {
+                return Err(GenesisConfigError::DuplicateValidatorKey { validator_key: hex::encode(compressed.as_bytes()) });
+            }
+        }
+
+        let mut total: u64 = 0;
+        for balance in self.accounts.iter().map(|account| account.balance).chain(self.stakes.iter().map(|stake| stake.balance)) {
+            let balance: u64 = balance.into();
+            if balance == 0 {
+                return Err(GenesisConfigError::ZeroBalance);
+            }
+            total = total.checked_add(balance).ok_or(GenesisConfigError::BalanceOverflow)?;
+        }
+        Coin::try_from(total).map_err(|_| GenesisConfigError::BalanceOverflow)?;
+
+        Ok(())
+    }
+
+    /// Hashes the canonical serialization of this config, so two nodes that each loaded a
+    /// genesis file can cheaply confirm they agree on the same chain before syncing further.
+    pub fn genesis_hash(&self) -> Blake2bHash {
+        let bytes = serde_json::to_vec(self).expect("GenesisConfig must always be serializable");
+        Blake2bHasher::new().digest(&bytes)
+    }
+}
+
+fn default_max_validator_slots() -> u32 {
+    DEFAULT_MAX_VALIDATOR_SLOTS
 }
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct GenesisStake {
+    #[serde(serialize_with = "serialize_nimiq_address")]
     #[serde(deserialize_with = "deserialize_nimiq_address")]
     pub staker_address: Address,
 
     #[serde(default)]
+    #[serde(serialize_with = "serialize_nimiq_address_opt")]
     #[serde(deserialize_with = "deserialize_nimiq_address_opt")]
     pub reward_address: Option<Address>,
 
+    #[serde(serialize_with = "serialize_coin")]
     #[serde(deserialize_with = "deserialize_coin")]
     pub balance: Coin,
 
+    #[serde(serialize_with = "serialize_bls_public_key")]
     #[serde(deserialize_with = "deserialize_bls_public_key")]
     pub validator_key: BlsPublicKey
 }
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct GenesisAccount {
+    #[serde(serialize_with = "serialize_nimiq_address")]
     #[serde(deserialize_with = "deserialize_nimiq_address")]
     pub address: Address,
 
+    #[serde(serialize_with = "serialize_coin")]
     #[serde(deserialize_with = "deserialize_coin")]
     pub balance: Coin,
 }
 
 
+pub fn serialize_nimiq_address<S>(address: &Address, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
+    address.to_user_friendly_address().serialize(serializer)
+}
+
 pub fn deserialize_nimiq_address<'de, D>(deserializer: D) -> Result<Address, D::Error> where D: Deserializer<'de> {
     let s = String::deserialize(deserializer)?;
     Address::from_user_friendly_address(&s)
         .map_err(|e| Error::custom(format!("{:?}", e)))
 }
 
+pub fn serialize_nimiq_address_opt<S>(address: &Option<Address>, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
+    address.as_ref().map(Address::to_user_friendly_address).serialize(serializer)
+}
+
 pub fn deserialize_nimiq_address_opt<'de, D>(deserializer: D) -> Result<Option<Address>, D::Error> where D: Deserializer<'de> {
     let opt: Option<String> = Option::deserialize(deserializer)?;
     if let Some(s) = opt {
@@ -71,9 +180,65 @@ pub fn deserialize_nimiq_address_opt<'de, D>(deserializer: D) -> Result<Option<A
     }
 }
 
+/// Always re-emits a bare Luna integer, regardless of whether the config was originally parsed
+/// from that form or from a denominated string.
+pub(crate) fn serialize_coin<S>(coin: &Coin, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
+    let value: u64 = (*coin).into();
+    value.serialize(serializer)
+}
+
+/// Accepts either a bare `u64` (interpreted as Luna, for backward compatibility) or a
+/// human-readable string like `"123.456"` or `"123.456 NIM"` (1 NIM = 100_000 Luna).
 pub(crate) fn deserialize_coin<'de, D>(deserializer: D) -> Result<Coin, D::Error> where D: Deserializer<'de> {
-    let value = u64::deserialize(deserializer)?;
-    Coin::try_from(value).map_err(Error::custom)
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum CoinValue {
+        Luna(u64),
+        Denominated(String),
+    }
+
+    match CoinValue::deserialize(deserializer)? {
+        CoinValue::Luna(value) => Coin::try_from(value).map_err(Error::custom),
+        CoinValue::Denominated(value) => parse_denominated_coin(&value).map_err(Error::custom),
+    }
+}
+
+/// Parses a human-readable NIM amount, mirroring Namada's denomination-aware amount parser: an
+/// optional unit suffix is stripped, the fractional part is right-padded with zeros to exactly
+/// the 5 digits Luna supports (so `"1.5"` is 150_000 Luna), and the result is rejected (rather than silently truncated) if it doesn't fit
+/// `Coin`.
+fn parse_denominated_coin(value: &str) -> Result<Coin, String> {
+    let trimmed = value.trim();
+    let amount = trimmed.split_whitespace().next().unwrap_or(trimmed)
+        .trim_end_matches(|c: char| c.is_alphabetic());
+
+    let mut parts = amount.splitn(2, '.');
+    let integer_part = parts.next().unwrap_or("");
+    let fractional_part = parts.next().unwrap_or("");
+
+    if fractional_part.len() > 5 {
+        return Err(format!("\"{}\" has more than 5 fractional digits", value));
+    }
+
+    let integer_part: u64 = integer_part.parse()
+        .map_err(|_| format!("\"{}\" is not a valid NIM amount", value))?;
+
+    let mut fractional_digits = fractional_part.to_string();
+    while fractional_digits.len() < 5 {
+        fractional_digits.push('0');
+    }
+    let fractional_part: u64 = fractional_digits.parse()
+        .map_err(|_| format!("\"{}\" is not a valid NIM amount", value))?;
+
+    let luna = integer_part.checked_mul(100_000)
+        .and_then(|whole| whole.checked_add(fractional_part))
+        .ok_or_else(|| format!("\"{}\" overflows the maximum Coin value", value))?;
+
+    Coin::try_from(luna).map_err(|e| format!("{:?}", e))
+}
+
+pub(crate) fn serialize_bls_public_key<S>(key: &BlsPublicKey, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
+    hex::encode(key.as_bytes()).serialize(serializer)
 }
 
 pub(crate) fn deserialize_bls_public_key<'de, D>(deserializer: D) -> Result<BlsPublicKey, D::Error> where D: Deserializer<'de> {
@@ -82,6 +247,10 @@ pub(crate) fn deserialize_bls_public_key<'de, D>(deserializer: D) -> Result<BlsP
     BlsPublicKey::from_slice(&pkey_raw).map_err(Error::custom)
 }
 
+pub(crate) fn serialize_bls_secret_key_opt<S>(key: &Option<BlsSecretKey>, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
+    key.as_ref().map(|key| hex::encode(key.as_bytes())).serialize(serializer)
+}
+
 pub(crate) fn deserialize_bls_secret_key_opt<'de, D>(deserializer: D) -> Result<Option<BlsSecretKey>, D::Error> where D: Deserializer<'de> {
     let opt: Option<String> = Option::deserialize(deserializer)?;
     if let Some(skey_hex) = opt {
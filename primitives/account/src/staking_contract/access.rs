@@ -0,0 +1,83 @@
+use std::collections::HashSet;
+
+use beserial::Deserialize;
+use bls::bls12_381::CompressedPublicKey as BlsPublicKey;
+use keys::Address;
+use transaction::Transaction;
+use transaction::account::staking_contract::StakingSelfTransactionType;
+
+use crate::{AccountError, Inherent, InherentType};
+use crate::staking_contract::StakingContract;
+use crate::staking_contract::verified::{VerifiedIncomingStakingTransaction, VerifiedOutgoingStakingTransaction};
+
+/// A single resource a staking transaction or inherent reads or writes, for the access-list
+/// declaration an executor needs to group non-conflicting work for parallel commit (à la
+/// EIP-2930 access lists / Solana's per-account locking). Stakers and validators are keyed
+/// differently in `StakingContract` (`Address` vs. `BlsPublicKey`), so unlike a single-account
+/// chain this needs two variants rather than one.
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub enum AccessedResource {
+    Staker(Address),
+    Validator(BlsPublicKey),
+}
+
+impl StakingContract {
+    // This module only classifies the resources of a single transaction or inherent. Neither
+    // `AccountTransactionInteraction` nor `AccountInherentInteraction` declares an access-list
+    // method, and nothing here groups staking transactions for parallel commit.
+    /// The resources an incoming staking transaction (a stake, or a retire/unpark/key-rotation
+    /// self transaction) reads or writes, mirroring `check_incoming_transaction`'s classification.
+    /// Unlike `check_incoming_transaction`, this takes `&self`: telling a `Stake`'s registration
+    /// apart from a top-up (see `VerifiedIncomingStakingTransaction::parse`) needs contract state.
+    ///
+    /// Nothing in this tree calls these three methods.
+    pub fn affected_addresses_incoming(&self, transaction: &Transaction) -> Result<HashSet<AccessedResource>, AccountError> {
+        let mut resources = HashSet::new();
+
+        match VerifiedIncomingStakingTransaction::parse(transaction, self)? {
+            VerifiedIncomingStakingTransaction::Stake(data) => {
+                resources.insert(AccessedResource::Staker(transaction.sender.clone()));
+                resources.insert(AccessedResource::Validator(data.validator_key));
+            },
+            VerifiedIncomingStakingTransaction::SelfTransaction { signer, ty, update_validator_key } => {
+                match ty {
+                    StakingSelfTransactionType::RetireStake | StakingSelfTransactionType::Unpark => {
+                        resources.insert(AccessedResource::Staker(signer));
+                    },
+                    StakingSelfTransactionType::UpdateValidatorKey => {
+                        let data = update_validator_key.expect("parsing a StakingSelfTransactionType::UpdateValidatorKey transaction always yields UpdateValidatorKeyData");
+                        resources.insert(AccessedResource::Validator(data.old_validator_key));
+                        resources.insert(AccessedResource::Validator(data.new_validator_key));
+                    },
+                }
+            },
+        }
+
+        Ok(resources)
+    }
+
+    /// The resources an outgoing (unstake) staking transaction reads or writes, mirroring
+    /// `check_outgoing_transaction`'s signer recovery.
+    pub fn affected_addresses_outgoing(transaction: &Transaction) -> Result<HashSet<AccessedResource>, AccountError> {
+        let mut resources = HashSet::new();
+        resources.insert(AccessedResource::Staker(VerifiedOutgoingStakingTransaction::parse(transaction)?.signer));
+        Ok(resources)
+    }
+
+    /// The resources a `Slash`/`Reward`/`FinalizeEpoch` inherent reads or writes. `FinalizeEpoch`
+    /// touches every validator still parked from the previous epoch, so unlike the transaction
+    /// variants above this needs `&self` to enumerate them.
+    pub fn affected_addresses_inherent(&self, inherent: &Inherent) -> Result<HashSet<AccessedResource>, AccountError> {
+        match inherent.ty {
+            InherentType::Slash | InherentType::Reward => {
+                let validator_key: BlsPublicKey = Deserialize::deserialize(&mut &inherent.data[..])?;
+                let mut resources = HashSet::new();
+                resources.insert(AccessedResource::Validator(validator_key));
+                Ok(resources)
+            },
+            InherentType::FinalizeEpoch => {
+                Ok(self.previous_epoch_parking.iter().cloned().map(AccessedResource::Validator).collect())
+            },
+        }
+    }
+}
@@ -1,16 +1,36 @@
 use std::collections::HashSet;
 use std::mem;
 
-use beserial::Deserialize;
-use transaction::account::staking_contract::{StakingTransactionData, StakingSelfTransactionType};
+use beserial::{Deserialize, Serialize};
+use bls::bls12_381::CompressedPublicKey as BlsPublicKey;
+use transaction::account::staking_contract::{StakingTransactionData, StakingSelfTransactionType, UpdateValidatorKeyData};
 
 use crate::{Account, AccountError, AccountTransactionInteraction, AccountType, Inherent, InherentType, StakingContract};
 use crate::inherent::AccountInherentInteraction;
 use crate::staking_contract::SlashReceipt;
+use crate::staking_contract::actions::reward::RewardDistributionReceipt;
+use crate::staking_contract::actions::validator::EvictedValidator;
 
 pub mod validator;
 pub mod staker;
+pub mod reward;
+
+/// Distinguishes a brand-new validator registration from a top-up stake to an existing
+/// validator, so that `revert_incoming_transaction` can undo the correct one. `evicted` is set
+/// when registration evicted the lowest-stake active validator to make room under
+/// `max_active_validators`.
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+struct StakeReceipt {
+    created: bool,
+    evicted: Option<EvictedValidator>,
+}
 
+// `check_incoming_transaction` is an associated function (no `&self`/`&mut self`) on
+// `AccountTransactionInteraction`, so it has no way to hand a parsed value to
+// `commit_incoming_transaction`/`revert_incoming_transaction`. Each method below parses the
+// transaction independently. The parsing logic factored out into
+// `VerifiedIncomingStakingTransaction`/`VerifiedOutgoingStakingTransaction` in `verified.rs` is
+// used by `access.rs`, not by the methods below.
 /// We need to distinguish three types of transactions:
 /// TODO: Should invalid incoming transactions just be no-ops?
 /// 1. Incoming transactions, which include:
@@ -46,14 +66,24 @@ impl AccountTransactionInteraction for StakingContract {
     fn check_incoming_transaction(transaction: &Transaction, _: u32) -> Result<(), AccountError> {
         // Do all static checks here.
         if transaction.sender != transaction.recipient {
-            // Stake transaction.
+            // Stake transaction. Whether the proof of possession needs to verify depends on
+            // whether this is a brand-new registration or a top-up of an already-registered
+            // validator, which we can't tell without `self`; that check happens in
+            // `commit_incoming_transaction` instead.
             StakingTransactionData::parse(transaction)?;
         } else {
-            // For retire & unpark transactions, we need to check a valid flag in the data field.
+            // For retire, unpark & update-key transactions, we need to check a valid flag in the data field.
             let ty: StakingSelfTransactionType = Deserialize::deserialize(&mut &transaction.data[..])?;
 
-            if transaction.data.len() != ty.serialized_size() {
-                return Err(AccountError::InvalidForTarget);
+            match ty {
+                StakingSelfTransactionType::UpdateValidatorKey => {
+                    UpdateValidatorKeyData::parse(transaction)?;
+                },
+                _ => {
+                    if transaction.data.len() != ty.serialized_size() {
+                        return Err(AccountError::InvalidForTarget);
+                    }
+                },
             }
         }
         Ok(())
@@ -61,10 +91,22 @@ impl AccountTransactionInteraction for StakingContract {
 
     fn commit_incoming_transaction(&mut self, transaction: &Transaction, block_height: u32) -> Result<Option<Vec<u8>>, AccountError> {
         if transaction.sender != transaction.recipient {
-            // Stake transaction
+            // Stake transaction. If the validator key is not registered yet, this is its
+            // registration: a new validator must prove possession of its own BLS key, which a
+            // top-up from an arbitrary delegating `staker_address` has no way to produce, so
+            // that check only applies here and not to the top-up branch below.
             let data = StakingTransactionData::parse(transaction)?;
-            Ok(self.stake(&transaction.sender, transaction.value, data.validator_key, data.reward_address)?
-                .map(|receipt| receipt.serialize_to_vec()))
+            if self.get_validator(&data.validator_key).is_some() {
+                self.stake(transaction.sender.clone(), transaction.value, &data.validator_key)?;
+                Ok(Some(StakeReceipt { created: false, evicted: None }.serialize_to_vec()))
+            } else {
+                if !data.validator_key.verify(&StakingContract::proof_of_knowledge_message(&data.validator_key), &data.proof_of_knowledge) {
+                    return Err(AccountError::InvalidForRecipient);
+                }
+                let reward_address = data.reward_address.unwrap_or_else(|| transaction.sender.clone());
+                let evicted = self.create_validator(data.validator_key, reward_address, transaction.value, block_height)?;
+                Ok(Some(StakeReceipt { created: true, evicted }.serialize_to_vec()))
+            }
         } else {
             let ty: StakingSelfTransactionType = Deserialize::deserialize(&mut &transaction.data[..])?;
             // XXX Get staker address from transaction proof. This violates the model that only the
@@ -81,6 +123,12 @@ impl AccountTransactionInteraction for StakingContract {
                 StakingSelfTransactionType::Unpark => {
                     Ok(Some(self.unpark_recipient(&staker_address, transaction.value)?.serialize_to_vec()))
                 },
+                StakingSelfTransactionType::UpdateValidatorKey => {
+                    // Rotates a validator's BLS key in place; the staker address is irrelevant here.
+                    let data = UpdateValidatorKeyData::parse(transaction)?;
+                    Ok(Some(self.update_validator_key(&data.old_validator_key, data.new_validator_key, &data.proof_of_knowledge)?
+                        .serialize_to_vec()))
+                },
             }
         }
     }
@@ -88,11 +136,15 @@ impl AccountTransactionInteraction for StakingContract {
     fn revert_incoming_transaction(&mut self, transaction: &Transaction, _block_height: u32, receipt: Option<&Vec<u8>>) -> Result<(), AccountError> {
         if transaction.sender != transaction.recipient {
             // Stake transaction
-            let receipt = match receipt {
-                Some(v) => Some(Deserialize::deserialize_from_vec(v)?),
-                _ => None
-            };
-            self.revert_stake(&transaction.sender, transaction.value, receipt)
+            let data = StakingTransactionData::parse(transaction)?;
+            let receipt: StakeReceipt = Deserialize::deserialize_from_vec(receipt.ok_or(AccountError::InvalidReceipt)?)?;
+
+            if receipt.created {
+                let reward_address = data.reward_address.unwrap_or_else(|| transaction.sender.clone());
+                self.revert_create_validator(data.validator_key, reward_address, transaction.value, receipt.evicted)
+            } else {
+                self.revert_stake(&transaction.sender, transaction.value, &data.validator_key)
+            }
         } else {
             let ty: StakingSelfTransactionType = Deserialize::deserialize(&mut &transaction.data[..])?;
             let staker_address = Self::get_signer(transaction)?;
@@ -110,6 +162,11 @@ impl AccountTransactionInteraction for StakingContract {
                     let receipt = Deserialize::deserialize_from_vec(receipt.ok_or(AccountError::InvalidReceipt)?)?;
                     self.revert_unpark_recipient(&staker_address, transaction.value, receipt)
                 },
+                StakingSelfTransactionType::UpdateValidatorKey => {
+                    let data = UpdateValidatorKeyData::parse(transaction)?;
+                    let receipt = Deserialize::deserialize_from_vec(receipt.ok_or(AccountError::InvalidReceipt)?)?;
+                    self.revert_update_validator_key(&data.new_validator_key, receipt)
+                },
             }
         }
     }
@@ -210,27 +267,33 @@ impl AccountTransactionInteraction for StakingContract {
 impl AccountInherentInteraction for StakingContract {
     fn check_inherent(&self, inherent: &Inherent, _block_height: u32) -> Result<(), AccountError> {
         trace!("check inherent: {:?}", inherent);
-        // Inherent slashes nothing
-        if inherent.value != Coin::ZERO {
-            return Err(AccountError::InvalidInherent);
-        }
 
         match inherent.ty {
             InherentType::Slash => {
+                // Inherent slashes nothing.
+                if inherent.value != Coin::ZERO {
+                    return Err(AccountError::InvalidInherent);
+                }
+
                 // Invalid data length
-                if inherent.data.len() != Address::SIZE {
+                if inherent.data.len() != BlsPublicKey::SIZE {
                     return Err(AccountError::InvalidInherent);
                 }
 
-                // Address doesn't exist in contract
-                let staker_address: Address = Deserialize::deserialize(&mut &inherent.data[..])?;
-                if !self.active_stake_by_address.contains_key(&staker_address) && !self.inactive_stake_by_address.contains_key(&staker_address) {
+                // Validator doesn't exist in contract
+                let validator_key: BlsPublicKey = Deserialize::deserialize(&mut &inherent.data[..])?;
+                if self.get_validator(&validator_key).is_none() {
                     return Err(AccountError::InvalidInherent);
                 }
 
                 Ok(())
             },
             InherentType::FinalizeEpoch => {
+                // Inherent moves no value of its own.
+                if inherent.value != Coin::ZERO {
+                    return Err(AccountError::InvalidInherent);
+                }
+
                 // Invalid data length
                 if !inherent.data.is_empty() {
                     return Err(AccountError::InvalidInherent);
@@ -238,7 +301,20 @@ impl AccountInherentInteraction for StakingContract {
 
                 Ok(())
             },
-            InherentType::Reward => Err(AccountError::InvalidForTarget)
+            InherentType::Reward => {
+                // Invalid data length
+                if inherent.data.len() != BlsPublicKey::SIZE {
+                    return Err(AccountError::InvalidInherent);
+                }
+
+                // Validator doesn't exist in contract
+                let validator_key: BlsPublicKey = Deserialize::deserialize(&mut &inherent.data[..])?;
+                if self.get_validator(&validator_key).is_none() {
+                    return Err(AccountError::InvalidInherent);
+                }
+
+                Ok(())
+            },
         }
     }
 
@@ -247,12 +323,11 @@ impl AccountInherentInteraction for StakingContract {
 
         match &inherent.ty {
             InherentType::Slash => {
-                // Simply add staker address to parking.
-                let staker_address: Address = Deserialize::deserialize(&mut &inherent.data[..])?;
+                // Park the validator and track it towards a ban (see `apply_slash`).
                 // TODO: The inherent might have originated from a fork proof for the previous epoch.
                 // Right now, we don't care and start the parking period in the epoch the proof has been submitted.
-                let newly_slashed = self.current_epoch_parking.insert(staker_address);
-                let receipt = SlashReceipt { newly_slashed };
+                let validator_key: BlsPublicKey = Deserialize::deserialize(&mut &inherent.data[..])?;
+                let receipt = self.apply_slash(&validator_key, policy::epoch_at(block_height));
                 Ok(Some(receipt.serialize_to_vec()))
             },
             InherentType::FinalizeEpoch => {
@@ -273,10 +348,17 @@ impl AccountInherentInteraction for StakingContract {
                     }
                 }
 
+                // Bans are epoch-scoped, so expired ones are cleared out on the same boundary.
+                self.prune_expired_bans(policy::epoch_at(block_height));
+
                 // Since finalized epochs cannot be reverted, we don't need any receipts.
                 Ok(None)
             },
-            _ => unreachable!(),
+            InherentType::Reward => {
+                let validator_key: BlsPublicKey = Deserialize::deserialize(&mut &inherent.data[..])?;
+                let receipt = self.distribute_reward(&validator_key, inherent.value, block_height)?;
+                Ok(Some(receipt.serialize_to_vec()))
+            },
         }
     }
 
@@ -284,22 +366,20 @@ impl AccountInherentInteraction for StakingContract {
         match &inherent.ty {
             InherentType::Slash => {
                 let receipt: SlashReceipt = Deserialize::deserialize_from_vec(&receipt.ok_or(AccountError::InvalidReceipt)?)?;
-                let staker_address: Address = Deserialize::deserialize(&mut &inherent.data[..])?;
-
-                // Only remove if it was not already slashed.
-                // I kept this in two nested if's for clarity.
-                if receipt.newly_slashed {
-                    let has_been_removed = self.current_epoch_parking.remove(&staker_address);
-                    if !has_been_removed {
-                        return Err(AccountError::InvalidInherent);
-                    }
-                }
+                let validator_key: BlsPublicKey = Deserialize::deserialize(&mut &inherent.data[..])?;
+
+                self.revert_slash(&validator_key, receipt)?;
             },
             InherentType::FinalizeEpoch => {
                 // We should not be able to revert finalized epochs!
                 return Err(AccountError::InvalidForTarget);
             },
-            _ => unreachable!(),
+            InherentType::Reward => {
+                let receipt: RewardDistributionReceipt = Deserialize::deserialize_from_vec(&receipt.ok_or(AccountError::InvalidReceipt)?)?;
+                let validator_key: BlsPublicKey = Deserialize::deserialize(&mut &inherent.data[..])?;
+
+                self.revert_distribute_reward(&validator_key, inherent.value, receipt)?;
+            },
         }
 
         Ok(())
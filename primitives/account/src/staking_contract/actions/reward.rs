@@ -0,0 +1,115 @@
+use beserial::{Deserialize, Serialize};
+use bls::bls12_381::CompressedPublicKey as BlsPublicKey;
+use keys::Address;
+use primitives::coin::Coin;
+
+use crate::{Account, AccountError, StakingContract};
+use crate::staking_contract::validator::REWARD_SCALE;
+
+/// Reverts `distribute_reward`.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub(super) struct RewardDistributionReceipt {
+    previous_reward_per_stake: u128,
+}
+
+/// Reverts `claim_reward`.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub(super) struct ClaimRewardReceipt {
+    previous_reward_debt: u128,
+    previous_pending_reward: Coin,
+}
+
+/// Reward distribution and claiming are:
+/// 1. Distribute: Credits a `Reward` inherent to a validator, splitting it across its
+///    delegators in proportion to their stake via the `reward_per_stake` accumulator.
+/// 2. Claim: Settles a staker's share of the accumulator and debits it from the contract's
+///    balance.
+impl StakingContract {
+    /// Credits `amount` to validator `validator_key`'s reward accumulator, to be split across
+    /// every staker currently delegating to it in proportion to their `active_stake_by_address`
+    /// entry. Bumping `reward_per_stake` touches only the one `Validator`, regardless of how
+    /// many stakers it has; each staker's share is settled lazily, either by `claim_reward` or
+    /// by `settle_reward_debt` the next time their stake changes.
+    pub fn distribute_reward(&mut self, validator_key: &BlsPublicKey, amount: Coin, _block_height: u32) -> Result<RewardDistributionReceipt, AccountError> {
+        let validator = self.get_validator(validator_key)
+            .ok_or(AccountError::InvalidForTarget)?;
+
+        self.balance = Account::balance_add(self.balance, amount)?;
+
+        // All checks passed, not allowed to fail from here on!
+        let mut validator_locked = validator.lock();
+        let previous_reward_per_stake = validator_locked.reward_per_stake;
+
+        let total_stake: u64 = validator_locked.active_stake_by_address.values()
+            .fold(Coin::ZERO, |sum, &stake| sum + stake)
+            .into();
+
+        if total_stake > 0 {
+            let amount: u64 = amount.into();
+            validator_locked.reward_per_stake += (u128::from(amount) * REWARD_SCALE) / u128::from(total_stake);
+        }
+        // If nobody is staked with this validator, the reward just sits in the contract's
+        // balance unclaimed - there is nobody to credit it to.
+
+        Ok(RewardDistributionReceipt { previous_reward_per_stake })
+    }
+
+    /// Reverts a reward distribution, restoring the validator's prior accumulator exactly.
+    pub(super) fn revert_distribute_reward(&mut self, validator_key: &BlsPublicKey, amount: Coin, receipt: RewardDistributionReceipt) -> Result<(), AccountError> {
+        let validator = self.get_validator(validator_key)
+            .ok_or(AccountError::InvalidForTarget)?;
+
+        self.balance = Account::balance_sub(self.balance, amount)?;
+
+        // All checks passed, not allowed to fail from here on!
+        validator.lock().reward_per_stake = receipt.previous_reward_per_stake;
+
+        Ok(())
+    }
+
+    /// The total reward `staker_address` could claim right now for stake delegated to
+    /// `validator_key`.
+    pub fn claimable_reward(&self, validator_key: &BlsPublicKey, staker_address: &Address) -> Coin {
+        match self.get_validator(validator_key) {
+            Some(validator) => validator.lock().claimable_reward(staker_address),
+            None => Coin::ZERO,
+        }
+    }
+
+    /// Settles and pays out `staker_address`'s claimable reward for stake delegated to
+    /// `validator_key`, resetting its debt. Like `unstake`, this only debits the contract's
+    /// internal `balance` and returns the claimed amount; it credits no account, and no staking
+    /// transaction type calls it.
+    pub fn claim_reward(&mut self, validator_key: &BlsPublicKey, staker_address: &Address) -> Result<(Coin, ClaimRewardReceipt), AccountError> {
+        let validator = self.get_validator(validator_key)
+            .ok_or(AccountError::InvalidForSender)?;
+
+        let mut validator_locked = validator.lock();
+        let previous_reward_debt = validator_locked.reward_debt_by_address.get(staker_address).copied().unwrap_or(0);
+        let previous_pending_reward = validator_locked.pending_reward_by_address.get(staker_address).copied().unwrap_or(Coin::ZERO);
+
+        // All checks passed, not allowed to fail from here on!
+        validator_locked.settle_reward_debt(staker_address);
+        let claimed = validator_locked.pending_reward_by_address.remove(staker_address).unwrap_or(Coin::ZERO);
+        drop(validator_locked);
+
+        self.balance = Account::balance_sub(self.balance, claimed)?;
+
+        Ok((claimed, ClaimRewardReceipt { previous_reward_debt, previous_pending_reward }))
+    }
+
+    /// Reverts a reward claim, restoring the staker's prior debt and pending reward exactly.
+    pub(super) fn revert_claim_reward(&mut self, validator_key: &BlsPublicKey, staker_address: &Address, claimed: Coin, receipt: ClaimRewardReceipt) -> Result<(), AccountError> {
+        let validator = self.get_validator(validator_key)
+            .ok_or(AccountError::InvalidForSender)?;
+
+        self.balance = Account::balance_add(self.balance, claimed)?;
+
+        // All checks passed, not allowed to fail from here on!
+        let mut validator_locked = validator.lock();
+        validator_locked.reward_debt_by_address.insert(staker_address.clone(), receipt.previous_reward_debt);
+        validator_locked.pending_reward_by_address.insert(staker_address.clone(), receipt.previous_pending_reward);
+
+        Ok(())
+    }
+}
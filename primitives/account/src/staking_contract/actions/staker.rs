@@ -44,6 +44,9 @@ impl StakingContract {
 
         // All checks passed, not allowed to fail from here on!
         let mut validator_locked = validator.lock();
+        // Settle the reward accrued on the old stake before it's replaced by the topped-up
+        // amount below, so reward_debt's next snapshot is taken against the correct balance.
+        validator_locked.settle_reward_debt(&staker_address);
         // We do not need to check for overflows here, because self.balance is always larger.
         validator_locked.balance += value;
         validator_locked.active_stake_by_address.entry(staker_address)
@@ -72,6 +75,10 @@ impl StakingContract {
         self.balance = Account::balance_sub(self.balance, value)?;
 
         // All checks passed, not allowed to fail from here on!
+        // Settle the reward accrued on the stake being removed before it's reduced below, so
+        // reward_debt's next snapshot is taken against the correct (post-removal) balance.
+        validator_locked.settle_reward_debt(staker_address);
+
         let mut stake = validator_locked.active_stake_by_address
             .get_mut(staker_address)
             .unwrap();
@@ -79,6 +86,7 @@ impl StakingContract {
 
         if stake.is_zero() {
             validator_locked.active_stake_by_address.remove(staker_address);
+            validator_locked.reward_debt_by_address.remove(staker_address);
         }
 
         Ok(())
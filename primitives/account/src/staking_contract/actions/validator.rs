@@ -6,14 +6,23 @@ use std::sync::Arc;
 use parking_lot::Mutex;
 
 use beserial::{Deserialize, Serialize};
-use bls::bls12_381::CompressedPublicKey as BlsPublicKey;
+use bls::bls12_381::{CompressedPublicKey as BlsPublicKey, CompressedSignature};
 use keys::Address;
 use primitives::coin::Coin;
 
 use crate::{Account, AccountError, StakingContract};
-use crate::staking_contract::{InactiveValidator, Validator};
+use crate::staking_contract::{BanRecord, InactiveValidator, SlashReceipt, SlashTrackingReceipt, Validator};
+use crate::staking_contract::{SLASH_WINDOW_EPOCHS, SLASH_BAN_THRESHOLD, BAN_DURATION_EPOCHS};
 use crate::staking_contract::actions::staker::InactiveStakeReceipt;
 
+/// Records the active validator evicted to make room for a newcomer once
+/// `max_active_validators` was reached, so `revert_create_validator` can re-promote it.
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub(super) struct EvictedValidator {
+    validator_key: BlsPublicKey,
+    retire_time: u32,
+}
+
 #[derive(Clone, Copy, Debug, Serialize, Deserialize, Eq, PartialEq)]
 pub(super) struct UnparkReceipt {
     current_epoch: bool,
@@ -37,6 +46,12 @@ pub(super) struct InactiveValidatorReceipt {
     retire_time: u32,
 }
 
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub(super) struct UpdateValidatorKeyReceipt {
+    old_validator_key: BlsPublicKey,
+    was_active: bool,
+}
+
 /// Actions concerning a validator are:
 /// 1. Create: Creates a validator entry.
 /// 2. Update: Updates reward address and key of the validator entry.
@@ -68,12 +83,38 @@ pub(super) struct InactiveValidatorReceipt {
 impl StakingContract {
     /// Creates a new validator entry.
     /// The initial stake can only be retrieved by dropping the validator again.
-    pub(super) fn create_validator(&mut self, validator_key: BlsPublicKey, reward_address: Address, initial_stake: Coin) -> Result<(), AccountError> {
+    ///
+    /// If the active set is already at `max_active_validators`, the newcomer must strictly
+    /// outbid the current lowest-stake active validator; that validator is then evicted to
+    /// `inactive_validators_by_key` (reversibly) to make room.
+    pub(super) fn create_validator(&mut self, validator_key: BlsPublicKey, reward_address: Address, initial_stake: Coin, block_height: u32) -> Result<Option<EvictedValidator>, AccountError> {
         if self.active_validators_by_key.contains_key(&validator_key)
             || self.inactive_validators_by_key.contains_key(&validator_key) {
             return Err(AccountError::InvalidForRecipient);
         }
 
+        // `max_validator_slots` bounds the validator set consistently in genesis and at runtime:
+        // a stake transaction that would register a brand-new validator beyond the cap is rejected.
+        if self.num_validators() as u32 >= self.max_validator_slots {
+            return Err(AccountError::InvalidForRecipient);
+        }
+
+        // `active_validators_sorted` is ordered highest to lowest stake, so the lowest-stake
+        // active validator is the last one in iteration order.
+        let to_evict = if self.active_validators_by_key.len() >= self.max_active_validators {
+            let lowest = self.active_validators_sorted.iter().next_back()
+                .expect("active_validators_sorted must be non-empty once max_active_validators is reached")
+                .clone();
+
+            if initial_stake <= lowest.lock().balance {
+                return Err(AccountError::InvalidForRecipient);
+            }
+
+            Some(lowest)
+        } else {
+            None
+        };
+
         self.balance = Account::balance_add(self.balance, initial_stake)?;
 
         // All checks passed, not allowed to fail from here on!
@@ -82,20 +123,44 @@ impl StakingContract {
             reward_address,
             validator_key,
             active_stake_by_address: Default::default(),
+            reward_per_stake: 0,
+            reward_debt_by_address: Default::default(),
+            pending_reward_by_address: Default::default(),
         }));
 
+        let evicted = to_evict.map(|evicted| {
+            let evicted_key = evicted.lock().validator_key.clone();
+            self.active_validators_sorted.remove(&evicted);
+            self.active_validators_by_key.remove(&evicted_key);
+            self.inactive_validators_by_key.insert(evicted_key.clone(), InactiveValidator {
+                validator: evicted,
+                retire_time: block_height,
+            });
+
+            EvictedValidator { validator_key: evicted_key, retire_time: block_height }
+        });
+
         self.active_validators_sorted.insert(Arc::clone(&validator));
         self.active_validators_by_key.insert(validator_key.clone(), validator);
-        Ok(())
+        Ok(evicted)
     }
 
-    /// Reverts creating a new validator entry.
-    pub(super) fn revert_create_validator(&mut self, validator_key: BlsPublicKey, reward_address: Address, initial_stake: Coin) -> Result<(), AccountError> {
+    /// Reverts creating a new validator entry, re-promoting the evicted validator (if any) back
+    /// into the active set.
+    pub(super) fn revert_create_validator(&mut self, validator_key: BlsPublicKey, reward_address: Address, initial_stake: Coin, evicted: Option<EvictedValidator>) -> Result<(), AccountError> {
         if let Some(validator) = self.active_validators_by_key.remove(&validator_key) {
             self.balance = Account::balance_sub(self.balance, initial_stake)?;
 
             // All checks passed, not allowed to fail from here on!
             self.active_validators_sorted.remove(&validator);
+
+            if let Some(evicted) = evicted {
+                let inactive_validator = self.inactive_validators_by_key.remove(&evicted.validator_key)
+                    .ok_or(AccountError::InvalidReceipt)?;
+                self.active_validators_sorted.insert(Arc::clone(&inactive_validator.validator));
+                self.active_validators_by_key.insert(evicted.validator_key, inactive_validator.validator);
+            }
+
             Ok(())
         } else {
             Err(AccountError::InvalidForRecipient)
@@ -180,6 +245,9 @@ impl StakingContract {
                 reward_address: receipt.reward_address,
                 validator_key,
                 active_stake_by_address,
+                reward_per_stake: 0,
+                reward_debt_by_address: Default::default(),
+                pending_reward_by_address: Default::default(),
             })),
             retire_time: receipt.retire_time,
         });
@@ -253,4 +321,120 @@ impl StakingContract {
 
         Ok(())
     }
+
+    /// Applies a slash inherent to a validator: parks it for the current epoch and, once the
+    /// number of slashes within `SLASH_WINDOW_EPOCHS` reaches `SLASH_BAN_THRESHOLD`, moves it into
+    /// `banned_validators` (permanently, if this is not the validator's first ban).
+    pub(super) fn apply_slash(&mut self, validator_key: &BlsPublicKey, epoch: u32) -> SlashReceipt {
+        let newly_slashed = self.current_epoch_parking.insert(validator_key.clone());
+
+        if self.banned_validators.contains_key(validator_key) {
+            // Already banned: no point in tracking further slashes towards a ban.
+            return SlashReceipt { newly_slashed, tracking: None };
+        }
+
+        let previous_history = self.slash_history.get(validator_key).cloned();
+        let previous_ban_strikes = self.ban_strikes.get(validator_key).copied();
+
+        let history = self.slash_history.entry(validator_key.clone()).or_insert_with(Vec::new);
+        history.push(epoch);
+        history.retain(|&slashed_epoch| slashed_epoch + SLASH_WINDOW_EPOCHS > epoch);
+
+        let mut newly_banned = false;
+        if history.len() as u32 >= SLASH_BAN_THRESHOLD {
+            let slash_count = history.len() as u32;
+
+            let strikes = self.ban_strikes.entry(validator_key.clone()).or_insert(0);
+            *strikes += 1;
+            let expiry_epoch = if *strikes >= 2 { None } else { Some(epoch + BAN_DURATION_EPOCHS) };
+
+            self.banned_validators.insert(validator_key.clone(), BanRecord {
+                start_epoch: epoch,
+                slash_count,
+                expiry_epoch,
+            });
+            self.slash_history.remove(validator_key);
+            newly_banned = true;
+        }
+
+        SlashReceipt {
+            newly_slashed,
+            tracking: Some(SlashTrackingReceipt { previous_history, previous_ban_strikes, newly_banned }),
+        }
+    }
+
+    /// Reverts a slash inherent, restoring the ban/slash-history bookkeeping exactly.
+    pub(super) fn revert_slash(&mut self, validator_key: &BlsPublicKey, receipt: SlashReceipt) -> Result<(), AccountError> {
+        if let Some(tracking) = receipt.tracking {
+            if tracking.newly_banned {
+                self.banned_validators.remove(validator_key);
+            }
+
+            match tracking.previous_history {
+                Some(history) => { self.slash_history.insert(validator_key.clone(), history); },
+                None => { self.slash_history.remove(validator_key); },
+            }
+
+            match tracking.previous_ban_strikes {
+                Some(strikes) => { self.ban_strikes.insert(validator_key.clone(), strikes); },
+                None => { self.ban_strikes.remove(validator_key); },
+            }
+        }
+
+        if receipt.newly_slashed {
+            if !self.current_epoch_parking.remove(validator_key) {
+                return Err(AccountError::InvalidInherent);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Rotates a validator's BLS key from `old_validator_key` to `new_validator_key`, without
+    /// unstaking: the `Validator` entry (balance, delegated stake, inactive bookkeeping) is
+    /// moved as-is to the new key, for an active or inactive validator alike. This is a
+    /// first-class self-transaction rather than a retire/re-stake cycle, so it preserves slot
+    /// priority and every delegator's stake untouched. The new key's proof of possession must
+    /// verify, and the new key must not already be registered.
+    pub(super) fn update_validator_key(&mut self, old_validator_key: &BlsPublicKey, new_validator_key: BlsPublicKey, proof_of_knowledge: &CompressedSignature) -> Result<UpdateValidatorKeyReceipt, AccountError> {
+        if self.active_validators_by_key.contains_key(&new_validator_key) || self.inactive_validators_by_key.contains_key(&new_validator_key) {
+            return Err(AccountError::InvalidForRecipient);
+        }
+
+        if !new_validator_key.verify(&StakingContract::proof_of_knowledge_message(&new_validator_key), proof_of_knowledge) {
+            return Err(AccountError::InvalidForRecipient);
+        }
+
+        // All checks passed, not allowed to fail from here on!
+        if let Some(validator) = self.active_validators_by_key.remove(old_validator_key) {
+            validator.lock().validator_key = new_validator_key.clone();
+            self.active_validators_by_key.insert(new_validator_key, validator);
+
+            Ok(UpdateValidatorKeyReceipt { old_validator_key: old_validator_key.clone(), was_active: true })
+        } else if let Some(inactive_validator) = self.inactive_validators_by_key.remove(old_validator_key) {
+            inactive_validator.validator.lock().validator_key = new_validator_key.clone();
+            self.inactive_validators_by_key.insert(new_validator_key, inactive_validator);
+
+            Ok(UpdateValidatorKeyReceipt { old_validator_key: old_validator_key.clone(), was_active: false })
+        } else {
+            Err(AccountError::InvalidForRecipient)
+        }
+    }
+
+    /// Reverts a validator key rotation, restoring the old key mapping exactly.
+    pub(super) fn revert_update_validator_key(&mut self, new_validator_key: &BlsPublicKey, receipt: UpdateValidatorKeyReceipt) -> Result<(), AccountError> {
+        if receipt.was_active {
+            let validator = self.active_validators_by_key.remove(new_validator_key)
+                .ok_or(AccountError::InvalidForRecipient)?;
+            validator.lock().validator_key = receipt.old_validator_key.clone();
+            self.active_validators_by_key.insert(receipt.old_validator_key, validator);
+        } else {
+            let inactive_validator = self.inactive_validators_by_key.remove(new_validator_key)
+                .ok_or(AccountError::InvalidForRecipient)?;
+            inactive_validator.validator.lock().validator_key = receipt.old_validator_key.clone();
+            self.inactive_validators_by_key.insert(receipt.old_validator_key, inactive_validator);
+        }
+
+        Ok(())
+    }
 }
\ No newline at end of file
@@ -16,8 +16,10 @@ use vrf::{VrfSeed, VrfUseCase, AliasMethod};
 use crate::{Account, AccountError, AccountTransactionInteraction, AccountType};
 use crate::inherent::{AccountInherentInteraction, Inherent, InherentType};
 
+pub mod access;
 pub mod actions;
 pub mod validator;
+pub mod verified;
 
 pub use self::validator::Validator;
 use parking_lot::Mutex;
@@ -34,11 +36,54 @@ pub struct InactiveValidator {
     pub retire_time: u32,
 }
 
+/// A validator that has been permanently (or temporarily, with an `expiry_epoch`) excluded from
+/// `select_validators` for chronic misbehaviour. See `banned_validators` on `StakingContract`.
 #[derive(Clone, Copy, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub struct BanRecord {
+    pub start_epoch: u32,
+    pub slash_count: u32,
+    /// `None` means the ban never expires.
+    pub expiry_epoch: Option<u32>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
 struct SlashReceipt {
     newly_slashed: bool,
+    /// `None` when the validator was already banned and slash tracking was skipped entirely.
+    tracking: Option<SlashTrackingReceipt>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+struct SlashTrackingReceipt {
+    previous_history: Option<Vec<u32>>,
+    previous_ban_strikes: Option<u32>,
+    newly_banned: bool,
 }
 
+/// Number of epochs over which recent slashes of the same validator are counted towards a ban.
+const SLASH_WINDOW_EPOCHS: u32 = 4;
+/// Number of slashes within `SLASH_WINDOW_EPOCHS` that triggers a ban.
+const SLASH_BAN_THRESHOLD: u32 = 3;
+/// Duration of a validator's first ban, in epochs. A validator's second ban is permanent.
+const BAN_DURATION_EPOCHS: u32 = 8;
+
+/// Default cap on the number of distinct validators (active + inactive) a `StakingContract`
+/// will admit, used when a contract is constructed without an explicit `max_validator_slots`.
+pub const DEFAULT_MAX_VALIDATOR_SLOTS: u32 = 1024;
+
+/// Default cap on the number of validators allowed in the *active* set at once, used when a
+/// contract is constructed without an explicit `max_active_validators`. Unlike
+/// `max_validator_slots`, which bounds the whole (active + inactive) registry, this bounds only
+/// `active_validators_by_key`; once it is reached, registering a new validator requires
+/// outbidding and evicting the current lowest-stake active validator.
+pub const DEFAULT_MAX_ACTIVE_VALIDATORS: usize = 256;
+
+/// Domain tag folded in front of a validator's own compressed public key before it is signed as
+/// a proof of knowledge (see `StakingContract::proof_of_knowledge_message`), so the signature
+/// can't be replayed as, say, a regular BLS signature authenticating some unrelated message that
+/// happens to match the serialized key bytes.
+const PROOF_OF_KNOWLEDGE_DOMAIN: &[u8] = b"nimiq-pos-validator-proof-of-knowledge";
+
 #[derive(Clone, Debug)]
 pub struct StakingContract {
     pub balance: Coin,
@@ -48,6 +93,22 @@ pub struct StakingContract {
     pub inactive_validators_by_key: BTreeMap<BlsPublicKey, InactiveValidator>,
     pub current_epoch_parking: HashSet<BlsPublicKey>,
     pub previous_epoch_parking: HashSet<BlsPublicKey>,
+    /// Validators banned for repeatedly cycling through parking without reforming.
+    pub banned_validators: BTreeMap<BlsPublicKey, BanRecord>,
+    /// Rolling window of recent slash epochs per validator, used to decide when a ban kicks in.
+    /// Cleared once a validator is banned.
+    slash_history: BTreeMap<BlsPublicKey, Vec<u32>>,
+    /// Number of times a validator has ever been banned, kept even after a ban expires so that a
+    /// second offence can be made permanent.
+    ban_strikes: BTreeMap<BlsPublicKey, u32>,
+    /// Upper bound on the number of distinct validators (active + inactive) this contract will
+    /// ever hold. Enforced by `create_validator`; must match the cap applied when the genesis
+    /// validator set was generated, or genesis and post-genesis state can disagree.
+    pub max_validator_slots: u32,
+    /// Upper bound on the number of validators admitted into the *active* set. Enforced by
+    /// `create_validator`: once reached, a new registration must strictly outbid (and evicts)
+    /// the current lowest-stake active validator instead of simply being added.
+    pub max_active_validators: usize,
     // Stake
     pub inactive_stake_by_address: HashMap<Address, InactiveStake>,
 }
@@ -59,6 +120,11 @@ impl StakingContract {
                 .map(|inactive_validator| &inactive_validator.validator))
     }
 
+    /// Total number of distinct validators (active + inactive) currently registered.
+    pub fn num_validators(&self) -> usize {
+        self.active_validators_by_key.len() + self.inactive_validators_by_key.len()
+    }
+
     pub fn get_balance(&self, staker_address: &Address) -> Coin {
         self.get_active_balance(staker_address) + self.get_inactive_balance(staker_address)
     }
@@ -71,20 +137,37 @@ impl StakingContract {
         self.inactive_stake_by_address.get(staker_address).map(|stake| stake.balance).unwrap_or(Coin::ZERO)
     }
 
+    /// Whether a validator is currently banned from `select_validators`.
+    pub fn is_banned(&self, validator_key: &BlsPublicKey) -> bool {
+        self.banned_validators.contains_key(validator_key)
+    }
+
+    /// Removes bans whose `expiry_epoch` has passed. Called once per finalized epoch.
+    pub(crate) fn prune_expired_bans(&mut self, epoch: u32) {
+        self.banned_validators.retain(|_, ban| ban.expiry_epoch.map_or(true, |expiry| expiry > epoch));
+    }
+
     pub fn select_validators(&self, seed: &VrfSeed) -> Slots {
         // TODO: Depending on the circumstances and parameters, it might be more efficient to store active stake in an unsorted Vec.
         // Then, we would not need to create the Vec here. But then, removal of stake is a O(n) operation.
         // Assuming that validator selection happens less frequently than stake removal, the current implementation might be ok.
-        let mut potential_validators = Vec::with_capacity(self.active_stake_sorted.len());
-        let mut weights: Vec<u64> = Vec::with_capacity(self.active_stake_sorted.len());
+        let mut potential_validators = Vec::with_capacity(self.active_validators_sorted.len());
+        let mut weights: Vec<u64> = Vec::with_capacity(self.active_validators_sorted.len());
 
         debug!("Select validators: num_slots = {}", policy::SLOTS);
 
-        // NOTE: `active_stake_sorted` is sorted from highest to lowest stake. `LookupTable`
+        // NOTE: `active_validators_sorted` is sorted from highest to lowest stake. `LookupTable`
         // expects the reverse ordering.
-        for validator in self.active_stake_sorted.iter() {
+        for validator in self.active_validators_sorted.iter() {
+            let validator_locked = validator.lock();
+
+            // Chronically misbehaving validators are banned from selection entirely.
+            if self.is_banned(&validator_locked.validator_key) {
+                continue;
+            }
+
             potential_validators.push(Arc::clone(validator));
-            weights.push(validator.balance.into());
+            weights.push(validator_locked.balance.into());
         }
 
         let mut slots_builder = SlotsBuilder::default();
@@ -94,22 +177,34 @@ impl StakingContract {
         for _ in 0 .. policy::SLOTS {
             let index = lookup.sample(&mut rng);
 
-            let active_stake = &potential_validators[index];
+            let validator_locked = potential_validators[index].lock();
 
             slots_builder.push(
-                active_stake.validator_key.clone(),
-                active_stake.staker_address.clone(),
-                active_stake.reward_address.clone()
+                validator_locked.validator_key.clone(),
+                validator_locked.reward_address.clone(),
+                validator_locked.reward_address.clone()
             );
         }
 
         slots_builder.build()
     }
 
+    /// Recovers the address that signed `transaction`'s proof. Neither the signature itself nor
+    /// the network the transaction is bound to is checked here.
     fn get_signer(transaction: &Transaction) -> Result<Address, AccountError> {
         let signature_proof: SignatureProof = Deserialize::deserialize(&mut &transaction.proof[..])?;
         Ok(signature_proof.compute_signer())
     }
+
+    /// The message a validator's BLS proof of knowledge signs over: `PROOF_OF_KNOWLEDGE_DOMAIN`
+    /// followed by its own compressed public key. Shared by the transaction builder (which signs
+    /// it) and every verification site in this crate (which check it), so the two can't drift
+    /// apart.
+    pub fn proof_of_knowledge_message(validator_key: &BlsPublicKey) -> Vec<u8> {
+        let mut message = PROOF_OF_KNOWLEDGE_DOMAIN.to_vec();
+        message.extend_from_slice(&validator_key.serialize_to_vec());
+        message
+    }
 }
 
 impl Serialize for StakingContract {
@@ -144,6 +239,31 @@ impl Serialize for StakingContract {
         size += SerializeWithLength::serialize::<u32, _>(&self.current_epoch_parking, writer)?;
         size += SerializeWithLength::serialize::<u32, _>(&self.previous_epoch_parking, writer)?;
 
+        // `banned_validators` is a `BTreeMap`, so iteration order is already the canonical,
+        // sorted-by-key order (mirroring how the inactive stakes above are sorted explicitly).
+        size += Serialize::serialize(&(self.banned_validators.len() as u32), writer)?;
+        for (validator_key, ban) in self.banned_validators.iter() {
+            size += Serialize::serialize(validator_key, writer)?;
+            size += Serialize::serialize(ban, writer)?;
+        }
+
+        // `slash_history`/`ban_strikes` are `BTreeMap`s too, so iteration order is already the
+        // canonical, sorted-by-key order, same as `banned_validators` above.
+        size += Serialize::serialize(&(self.slash_history.len() as u32), writer)?;
+        for (validator_key, epochs) in self.slash_history.iter() {
+            size += Serialize::serialize(validator_key, writer)?;
+            size += Serialize::serialize(epochs, writer)?;
+        }
+
+        size += Serialize::serialize(&(self.ban_strikes.len() as u32), writer)?;
+        for (validator_key, strikes) in self.ban_strikes.iter() {
+            size += Serialize::serialize(validator_key, writer)?;
+            size += Serialize::serialize(strikes, writer)?;
+        }
+
+        size += Serialize::serialize(&self.max_validator_slots, writer)?;
+        size += Serialize::serialize(&(self.max_active_validators as u32), writer)?;
+
         Ok(size)
     }
 
@@ -169,6 +289,27 @@ impl Serialize for StakingContract {
         size += SerializeWithLength::serialized_size::<u32>(&self.current_epoch_parking);
         size += SerializeWithLength::serialized_size::<u32>(&self.previous_epoch_parking);
 
+        size += Serialize::serialized_size(&0u32);
+        for (validator_key, ban) in self.banned_validators.iter() {
+            size += Serialize::serialized_size(validator_key);
+            size += Serialize::serialized_size(ban);
+        }
+
+        size += Serialize::serialized_size(&0u32);
+        for (validator_key, epochs) in self.slash_history.iter() {
+            size += Serialize::serialized_size(validator_key);
+            size += Serialize::serialized_size(epochs);
+        }
+
+        size += Serialize::serialized_size(&0u32);
+        for (validator_key, strikes) in self.ban_strikes.iter() {
+            size += Serialize::serialized_size(validator_key);
+            size += Serialize::serialized_size(strikes);
+        }
+
+        size += Serialize::serialized_size(&self.max_validator_slots);
+        size += Serialize::serialized_size(&0u32);
+
         size
     }
 }
@@ -204,13 +345,45 @@ impl Deserialize for StakingContract {
         let current_epoch_parking: HashSet<Address> = DeserializeWithLength::deserialize::<u32, _>(reader)?;
         let last_epoch_parking: HashSet<Address> = DeserializeWithLength::deserialize::<u32, _>(reader)?;
 
+        let mut banned_validators = BTreeMap::new();
+        let num_banned_validators: u32 = Deserialize::deserialize(reader)?;
+        for _ in 0..num_banned_validators {
+            let validator_key = Deserialize::deserialize(reader)?;
+            let ban = Deserialize::deserialize(reader)?;
+            banned_validators.insert(validator_key, ban);
+        }
+
+        let mut slash_history = BTreeMap::new();
+        let num_slash_histories: u32 = Deserialize::deserialize(reader)?;
+        for _ in 0..num_slash_histories {
+            let validator_key = Deserialize::deserialize(reader)?;
+            let epochs = Deserialize::deserialize(reader)?;
+            slash_history.insert(validator_key, epochs);
+        }
+
+        let mut ban_strikes = BTreeMap::new();
+        let num_ban_strikes: u32 = Deserialize::deserialize(reader)?;
+        for _ in 0..num_ban_strikes {
+            let validator_key = Deserialize::deserialize(reader)?;
+            let strikes = Deserialize::deserialize(reader)?;
+            ban_strikes.insert(validator_key, strikes);
+        }
+
+        let max_validator_slots: u32 = Deserialize::deserialize(reader)?;
+        let max_active_validators: u32 = Deserialize::deserialize(reader)?;
+
         Ok(StakingContract {
             balance,
             active_stake_sorted,
             active_stake_by_address,
             inactive_stake_by_address,
             current_epoch_parking,
-            previous_epoch_parking: last_epoch_parking
+            previous_epoch_parking: last_epoch_parking,
+            banned_validators,
+            slash_history,
+            ban_strikes,
+            max_validator_slots,
+            max_active_validators: max_active_validators as usize,
         })
     }
 }
@@ -246,6 +419,8 @@ impl Default for StakingContract {
             inactive_stake_by_address: HashMap::new(),
             current_epoch_parking: HashSet::new(),
             previous_epoch_parking: HashSet::new(),
+            max_validator_slots: DEFAULT_MAX_VALIDATOR_SLOTS,
+            max_active_validators: DEFAULT_MAX_ACTIVE_VALIDATORS,
         }
     }
 }
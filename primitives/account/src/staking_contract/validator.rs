@@ -1,17 +1,32 @@
 use std::cmp::Ordering;
 use std::collections::BTreeMap;
+use std::convert::TryFrom;
 
 use beserial::{Deserialize, Serialize};
 use bls::bls12_381::CompressedPublicKey as BlsPublicKey;
 use keys::Address;
 use primitives::coin::Coin;
 
+/// Fixed-point scale applied to `Validator::reward_per_stake`. Rewards accrue in units of
+/// `1 / REWARD_SCALE` stake-coin, so the `amount / total_active_stake` division done by
+/// `distribute_reward` keeps enough precision that the rounding remainder per distribution is
+/// negligible, rather than being truncated away to zero by plain integer division.
+pub(crate) const REWARD_SCALE: u128 = 1_000_000_000_000_000_000;
+
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Validator {
     pub balance: Coin,
     pub reward_address: Address,
     pub validator_key: BlsPublicKey,
     pub active_stake_by_address: BTreeMap<Address, Coin>,
+    /// Accumulated reward per unit of stake, scaled by `REWARD_SCALE`. Bumped by
+    /// `StakingContract::distribute_reward` each time this validator is rewarded.
+    pub reward_per_stake: u128,
+    /// Snapshot of `stake * reward_per_stake / REWARD_SCALE` taken the last time each staker's balance or
+    /// pending reward changed, so that only the reward accrued since then counts as new.
+    pub reward_debt_by_address: BTreeMap<Address, u128>,
+    /// Reward already settled out of the accumulator but not yet paid out via `claim_reward`.
+    pub pending_reward_by_address: BTreeMap<Address, Coin>,
 }
 
 impl PartialEq for Validator {
@@ -36,3 +51,38 @@ impl Ord for Validator {
             .then_with(|| self.validator_key.cmp(&other.validator_key))
     }
 }
+
+impl Validator {
+    /// Settles `staker_address`'s outstanding reward against the current `reward_per_stake`
+    /// accumulator into `pending_reward_by_address`, then re-snapshots its `reward_debt` at its
+    /// current stake. Must be called before `active_stake_by_address` is updated for this
+    /// address - otherwise the stake that earned the reward is gone the moment it's replaced by
+    /// a different amount, over- or under-paying the staker for the epoch the change happened in
+    /// (the boundary bug this accumulator scheme exists to avoid).
+    pub(crate) fn settle_reward_debt(&mut self, staker_address: &Address) {
+        let stake: u64 = self.active_stake_by_address.get(staker_address).copied().unwrap_or(Coin::ZERO).into();
+        let accrued = u128::from(stake) * self.reward_per_stake / REWARD_SCALE;
+        let reward_debt = self.reward_debt_by_address.get(staker_address).copied().unwrap_or(0);
+        let settled = accrued.saturating_sub(reward_debt);
+
+        if settled > 0 {
+            let settled = Coin::try_from(settled as u64).expect("reward settlement exceeds maximum Coin value");
+            *self.pending_reward_by_address.entry(staker_address.clone()).or_insert(Coin::ZERO) += settled;
+        }
+
+        self.reward_debt_by_address.insert(staker_address.clone(), accrued);
+    }
+
+    /// The total reward `staker_address` could claim right now: already-settled
+    /// `pending_reward_by_address` plus whatever has accrued since its last `reward_debt`
+    /// snapshot.
+    pub fn claimable_reward(&self, staker_address: &Address) -> Coin {
+        let stake: u64 = self.active_stake_by_address.get(staker_address).copied().unwrap_or(Coin::ZERO).into();
+        let accrued = u128::from(stake) * self.reward_per_stake / REWARD_SCALE;
+        let reward_debt = self.reward_debt_by_address.get(staker_address).copied().unwrap_or(0);
+        let unsettled = accrued.saturating_sub(reward_debt);
+
+        let pending: u64 = self.pending_reward_by_address.get(staker_address).copied().unwrap_or(Coin::ZERO).into();
+        Coin::try_from(pending + unsettled as u64).expect("claimable reward exceeds maximum Coin value")
+    }
+}
@@ -0,0 +1,85 @@
+use beserial::Deserialize;
+use keys::Address;
+use transaction::Transaction;
+use transaction::account::staking_contract::{StakingSelfTransactionType, StakingTransactionData, UpdateValidatorKeyData};
+
+use crate::AccountError;
+use crate::staking_contract::StakingContract;
+
+/// The parsed payload of an incoming staking transaction (`sender != recipient`: a new
+/// validator's registration or a top-up of an existing one's stake; `sender == recipient`: a self
+/// transaction together with the `signer` recovered from its proof). The registration's proof of
+/// knowledge is verified; the self transaction's signature is not (see
+/// `StakingContract::get_signer`).
+///
+/// This is the same parsing/signature-recovery logic `AccountTransactionInteraction`'s
+/// `check_incoming_transaction`/`commit_incoming_transaction` apply inline; it's factored out
+/// here purely so `StakingContract::affected_addresses_incoming` (see `access.rs`) doesn't have
+/// to duplicate it. `check_incoming_transaction` itself takes no `&self`, so there is no way to
+/// carry a parsed value from it into `commit_incoming_transaction` across the trait boundary;
+/// each call still parses independently. Unlike `check_incoming_transaction`, `parse` does take
+/// a `&StakingContract`, since telling a registration from a top-up (and so whether a proof of
+/// knowledge is required) needs `get_validator`.
+pub enum VerifiedIncomingStakingTransaction {
+    Stake(StakingTransactionData),
+    SelfTransaction {
+        signer: Address,
+        ty: StakingSelfTransactionType,
+        update_validator_key: Option<UpdateValidatorKeyData>,
+    },
+}
+
+impl VerifiedIncomingStakingTransaction {
+    pub fn parse(transaction: &Transaction, contract: &StakingContract) -> Result<Self, AccountError> {
+        if transaction.sender != transaction.recipient {
+            // Stake transaction. Mirrors `commit_incoming_transaction`: a new validator must
+            // prove possession of its own BLS key, but a top-up of one that's already registered
+            // doesn't, since an arbitrary delegating `staker_address` has no way to produce that
+            // proof.
+            let data = StakingTransactionData::parse(transaction)?;
+            if contract.get_validator(&data.validator_key).is_none()
+                && !data.validator_key.verify(&StakingContract::proof_of_knowledge_message(&data.validator_key), &data.proof_of_knowledge)
+            {
+                return Err(AccountError::InvalidForRecipient);
+            }
+            Ok(VerifiedIncomingStakingTransaction::Stake(data))
+        } else {
+            // For retire, unpark & update-key transactions, we need to check a valid flag in the data field.
+            let ty: StakingSelfTransactionType = Deserialize::deserialize(&mut &transaction.data[..])?;
+
+            let update_validator_key = match ty {
+                StakingSelfTransactionType::UpdateValidatorKey => Some(UpdateValidatorKeyData::parse(transaction)?),
+                _ => {
+                    if transaction.data.len() != ty.serialized_size() {
+                        return Err(AccountError::InvalidForTarget);
+                    }
+                    None
+                },
+            };
+
+            // XXX Get staker address from transaction proof. This violates the model that only the
+            // sender account should evaluate the proof. However, retire/unpark are self transactions, so
+            // this contract is both sender and receiver.
+            let signer = StakingContract::get_signer(transaction)?;
+
+            Ok(VerifiedIncomingStakingTransaction::SelfTransaction { signer, ty, update_validator_key })
+        }
+    }
+}
+
+/// The signer of an outgoing (unstake) staking transaction, recovered from its proof without
+/// verifying the signature.
+///
+/// Same signer-recovery logic `check_outgoing_transaction`/`commit_outgoing_transaction` apply
+/// inline, factored out for `StakingContract::affected_addresses_outgoing` (see `access.rs`).
+pub struct VerifiedOutgoingStakingTransaction {
+    pub signer: Address,
+}
+
+impl VerifiedOutgoingStakingTransaction {
+    pub fn parse(transaction: &Transaction) -> Result<Self, AccountError> {
+        Ok(VerifiedOutgoingStakingTransaction {
+            signer: StakingContract::get_signer(transaction)?,
+        })
+    }
+}
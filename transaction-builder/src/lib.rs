@@ -1,3 +1,4 @@
+extern crate nimiq_account as account;
 extern crate nimiq_bls as bls;
 extern crate nimiq_hash as hash;
 extern crate nimiq_keys as keys;
@@ -7,18 +8,23 @@ extern crate nimiq_utils as utils;
 
 use failure::Fail;
 
-use bls::bls12_381::KeyPair as BlsKeyPair;
+use account::staking_contract::StakingContract;
+use bls::bls12_381::{CompressedPublicKey as BlsPublicKey, KeyPair as BlsKeyPair};
 use keys::{Address, KeyPair};
 use primitives::account::AccountType;
 use primitives::coin::Coin;
 use primitives::networks::NetworkId;
 use transaction::Transaction;
 
+use crate::recipient::staking_contract::StakingTransaction;
+
 pub use crate::proof::TransactionProofBuilder;
 pub use crate::recipient::Recipient;
+pub use crate::verified::{TransactionVerificationError, UnverifiedTransaction, VerifiedTransaction};
 
 pub mod recipient;
 pub mod proof;
+pub mod verified;
 
 #[derive(Debug, Fail)]
 pub enum TransactionBuilderError {
@@ -34,6 +40,16 @@ pub enum TransactionBuilderError {
     NoNetworkId,
     #[fail(display = "The sender is invalid for this recipient.")]
     InvalidSender,
+    #[fail(display = "The signer does not have enough active stake for this transaction.")]
+    InsufficientActiveStake,
+    #[fail(display = "This validator is not currently parked.")]
+    ValidatorNotParked,
+    #[fail(display = "This validator key is not registered in the staking contract.")]
+    UnknownValidator,
+    #[fail(display = "The transaction value does not match the validator's parked stake.")]
+    InvalidUnparkValue,
+    #[fail(display = "The proof of knowledge does not verify against the validator's BLS key.")]
+    InvalidProofOfKnowledge,
 }
 
 #[derive(Default)]
@@ -88,6 +104,28 @@ impl TransactionBuilder {
         self
     }
 
+    /// Dry-runs the staking operation configured on this builder against a read-only
+    /// `StakingContract` snapshot, so wallets can surface a precise failure reason without
+    /// round-tripping to a node and getting back a generic `AccountError` at apply time. A no-op
+    /// for non-staking recipients. `unpark_validator_key` must be supplied when validating an
+    /// unpark transaction, since the validator being unparked is not itself part of the wire data.
+    ///
+    /// Delegates to `StakingTransaction::validate_against`, the same check
+    /// `StakingRecipientBuilder::validate_against` uses, so the two builders can't drift apart on
+    /// what counts as a valid staking transaction.
+    pub fn validate_against(&self, contract: &StakingContract, unpark_validator_key: Option<&BlsPublicKey>) -> Result<(), TransactionBuilderError> {
+        let data = match self.recipient.as_ref().ok_or(TransactionBuilderError::NoRecipient)? {
+            Recipient::Staking { data, .. } => data,
+            _ => return Ok(()),
+        };
+
+        let sender = self.sender.as_ref().ok_or(TransactionBuilderError::NoSender)?;
+        let value = self.value.ok_or(TransactionBuilderError::NoValue)?;
+        let fee = self.fee.unwrap_or(Coin::ZERO);
+
+        data.validate_against(contract, sender, value, fee, unpark_validator_key)
+    }
+
     pub fn generate(self) -> Result<TransactionProofBuilder, TransactionBuilderError> {
         let sender = self.sender.ok_or(TransactionBuilderError::NoSender)?;
         let recipient = self.recipient.ok_or(TransactionBuilderError::NoRecipient)?;
@@ -145,8 +183,9 @@ impl TransactionBuilder {
         let proof_builder = builder.generate().unwrap();
         match proof_builder {
             TransactionProofBuilder::Basic(mut builder) => {
+                builder.with_network_id(network_id);
                 builder.sign_with_key_pair(&key_pair);
-                builder.generate().unwrap()
+                builder.generate().unwrap().into_transaction()
             },
             _ => unreachable!(),
         }
@@ -167,8 +206,9 @@ impl TransactionBuilder {
         let proof_builder = builder.generate().unwrap();
         match proof_builder {
             TransactionProofBuilder::Basic(mut builder) => {
+                builder.with_network_id(network_id);
                 builder.sign_with_key_pair(&key_pair);
-                builder.generate().unwrap()
+                builder.generate().unwrap().into_transaction()
             },
             _ => unreachable!(),
         }
@@ -190,8 +230,33 @@ impl TransactionBuilder {
         let proof_builder = builder.generate().unwrap();
         match proof_builder {
             TransactionProofBuilder::Staking(mut builder) => {
+                builder.with_network_id(network_id);
+                builder.sign_with_key_pair(&key_pair);
+                builder.generate().unwrap().into_transaction()
+            },
+            _ => unreachable!(),
+        }
+    }
+
+    pub fn new_update_validator_key(key_pair: &KeyPair, staking_contract: Address, old_validator_key: BlsPublicKey, new_validator_key: &BlsKeyPair, fee: Coin, validity_start_height: u32, network_id: NetworkId) -> Transaction {
+        let mut recipient = Recipient::new_staking_builder(staking_contract.clone());
+        recipient.update_validator_key(old_validator_key, new_validator_key);
+
+        let mut builder = Self::new();
+        builder.with_sender(staking_contract)
+            .with_sender_type(AccountType::Staking)
+            .with_recipient(recipient.generate().unwrap())
+            .with_value(Coin::ZERO)
+            .with_fee(fee)
+            .with_validity_start_height(validity_start_height)
+            .with_network_id(network_id);
+
+        let proof_builder = builder.generate().unwrap();
+        match proof_builder {
+            TransactionProofBuilder::Staking(mut builder) => {
+                builder.with_network_id(network_id);
                 builder.sign_with_key_pair(&key_pair);
-                builder.generate().unwrap()
+                builder.generate().unwrap().into_transaction()
             },
             _ => unreachable!(),
         }
@@ -213,8 +278,9 @@ impl TransactionBuilder {
         let proof_builder = builder.generate().unwrap();
         match proof_builder {
             TransactionProofBuilder::Staking(mut builder) => {
+                builder.with_network_id(network_id);
                 builder.sign_with_key_pair(&key_pair);
-                builder.generate().unwrap()
+                builder.generate().unwrap().into_transaction()
             },
             _ => unreachable!(),
         }
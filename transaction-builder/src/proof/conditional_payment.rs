@@ -0,0 +1,208 @@
+use beserial::{Deserialize, ReadBytesExt, Serialize, SerializingError, WriteBytesExt};
+use hash::{Blake2bHasher, Hasher, Sha256Hasher};
+use keys::KeyPair;
+use transaction::{SignatureProof, Transaction};
+use transaction::account::htlc_contract::{AnyHash, HashAlgorithm};
+
+use crate::recipient::conditional_payment::{Condition, MAX_CONDITION_DEPTH};
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, Eq, PartialEq)]
+enum ConditionProofType {
+    AfterBlock,
+    HashPreimage,
+    SignedBy,
+    And,
+    Or,
+}
+
+/// A witness tree shaped exactly like the `Condition` it proves: one node per `Condition` node,
+/// carrying whatever evidence that node's release condition needs - a pre-image, a signature, or
+/// (for `And`/`Or`) the combined proofs of its children.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ConditionProof {
+    AfterBlock,
+    HashPreimage(AnyHash),
+    SignedBy(SignatureProof),
+    And(Box<ConditionProof>, Box<ConditionProof>),
+    Or(Box<ConditionProof>, Box<ConditionProof>),
+}
+
+impl ConditionProof {
+    /// Decodes a `ConditionProof`, rejecting a tree deeper than `max_depth` rather than
+    /// recursing arbitrarily far into attacker-controlled wire data.
+    fn deserialize_bounded<R: ReadBytesExt>(reader: &mut R, max_depth: u8) -> Result<Self, SerializingError> {
+        if max_depth == 0 {
+            return Err(SerializingError::InvalidValue);
+        }
+        let proof_type: ConditionProofType = Deserialize::deserialize(reader)?;
+        Ok(match proof_type {
+            ConditionProofType::AfterBlock => ConditionProof::AfterBlock,
+            ConditionProofType::HashPreimage => ConditionProof::HashPreimage(Deserialize::deserialize(reader)?),
+            ConditionProofType::SignedBy => ConditionProof::SignedBy(Deserialize::deserialize(reader)?),
+            ConditionProofType::And | ConditionProofType::Or => {
+                let child_depth = max_depth.checked_sub(1).ok_or(SerializingError::InvalidValue)?;
+                let lhs = Box::new(ConditionProof::deserialize_bounded(reader, child_depth)?);
+                let rhs = Box::new(ConditionProof::deserialize_bounded(reader, child_depth)?);
+                if proof_type == ConditionProofType::And {
+                    ConditionProof::And(lhs, rhs)
+                } else {
+                    ConditionProof::Or(lhs, rhs)
+                }
+            },
+        })
+    }
+}
+
+impl Serialize for ConditionProof {
+    fn serialize<W: WriteBytesExt>(&self, writer: &mut W) -> Result<usize, SerializingError> {
+        let mut size = 0;
+        match self {
+            ConditionProof::AfterBlock => {
+                size += ConditionProofType::AfterBlock.serialize(writer)?;
+            },
+            ConditionProof::HashPreimage(pre_image) => {
+                size += ConditionProofType::HashPreimage.serialize(writer)?;
+                size += pre_image.serialize(writer)?;
+            },
+            ConditionProof::SignedBy(signature) => {
+                size += ConditionProofType::SignedBy.serialize(writer)?;
+                size += signature.serialize(writer)?;
+            },
+            ConditionProof::And(lhs, rhs) => {
+                size += ConditionProofType::And.serialize(writer)?;
+                size += lhs.serialize(writer)?;
+                size += rhs.serialize(writer)?;
+            },
+            ConditionProof::Or(lhs, rhs) => {
+                size += ConditionProofType::Or.serialize(writer)?;
+                size += lhs.serialize(writer)?;
+                size += rhs.serialize(writer)?;
+            },
+        }
+        Ok(size)
+    }
+
+    fn serialized_size(&self) -> usize {
+        let mut size = match self {
+            ConditionProof::AfterBlock => ConditionProofType::AfterBlock.serialized_size(),
+            ConditionProof::HashPreimage(_) => ConditionProofType::HashPreimage.serialized_size(),
+            ConditionProof::SignedBy(_) => ConditionProofType::SignedBy.serialized_size(),
+            ConditionProof::And(..) => ConditionProofType::And.serialized_size(),
+            ConditionProof::Or(..) => ConditionProofType::Or.serialized_size(),
+        };
+        match self {
+            ConditionProof::AfterBlock => {},
+            ConditionProof::HashPreimage(pre_image) => size += pre_image.serialized_size(),
+            ConditionProof::SignedBy(signature) => size += signature.serialized_size(),
+            ConditionProof::And(lhs, rhs) | ConditionProof::Or(lhs, rhs) => {
+                size += lhs.serialized_size();
+                size += rhs.serialized_size();
+            },
+        }
+        size
+    }
+}
+
+impl Deserialize for ConditionProof {
+    fn deserialize<R: ReadBytesExt>(reader: &mut R) -> Result<Self, SerializingError> {
+        ConditionProof::deserialize_bounded(reader, MAX_CONDITION_DEPTH)
+    }
+}
+
+impl Condition {
+    /// Recursively checks `witness` against `self`, returning `true` only if `witness` has
+    /// exactly the same shape as `self` and every leaf it reaches is actually satisfied:
+    /// an `AfterBlock` whose height has passed, a `HashPreimage` whose pre-image hashes to the
+    /// stored root in `hash_count` steps, or a `SignedBy` whose signature both verifies against
+    /// `message` and recovers to the stored address. `And`/`Or` combine their children the usual
+    /// way. A shape mismatch between `self` and `witness` - e.g. a `SignedBy` proof offered
+    /// against a `HashPreimage` condition - is never satisfied.
+    pub fn evaluate(&self, witness: &ConditionProof, message: &[u8], block_height: u32) -> bool {
+        match (self, witness) {
+            (Condition::AfterBlock(height), ConditionProof::AfterBlock) => block_height >= *height,
+            (Condition::HashPreimage { hash_algorithm, hash_root, hash_count }, ConditionProof::HashPreimage(pre_image)) => {
+                hashes_to_root(*hash_algorithm, pre_image, *hash_count, hash_root)
+            },
+            (Condition::SignedBy(address), ConditionProof::SignedBy(signature)) => {
+                signature.verify(message) && signature.compute_signer() == *address
+            },
+            (Condition::And(lhs, rhs), ConditionProof::And(lhs_witness, rhs_witness)) => {
+                lhs.evaluate(lhs_witness, message, block_height) && rhs.evaluate(rhs_witness, message, block_height)
+            },
+            (Condition::Or(lhs, rhs), ConditionProof::Or(lhs_witness, rhs_witness)) => {
+                lhs.evaluate(lhs_witness, message, block_height) || rhs.evaluate(rhs_witness, message, block_height)
+            },
+            _ => false,
+        }
+    }
+}
+
+fn hashes_to_root(hash_algorithm: HashAlgorithm, pre_image: &AnyHash, hash_count: u8, hash_root: &AnyHash) -> bool {
+    let mut digest: [u8; 32] = pre_image.clone().into();
+    for _ in 0..hash_count {
+        digest = match hash_algorithm {
+            HashAlgorithm::Blake2b => Blake2bHasher::new().digest(&digest).into(),
+            HashAlgorithm::Sha256 => Sha256Hasher::new().digest(&digest).into(),
+        };
+    }
+    AnyHash::from(digest) == *hash_root
+}
+
+pub struct ConditionalPaymentProofBuilder {
+    pub transaction: Transaction,
+    proof: Option<ConditionProof>,
+}
+
+impl ConditionalPaymentProofBuilder {
+    pub fn new(transaction: Transaction) -> Self {
+        ConditionalPaymentProofBuilder {
+            transaction,
+            proof: None,
+        }
+    }
+
+    pub fn signature_with_key_pair(&self, key_pair: &KeyPair) -> SignatureProof {
+        let signature = key_pair.sign(self.transaction.serialize_content().as_slice());
+        SignatureProof::from(key_pair.public, signature)
+    }
+
+    pub fn with_proof(&mut self, proof: ConditionProof) -> &mut Self {
+        self.proof = Some(proof);
+        self
+    }
+
+    pub fn after_block(&mut self) -> &mut Self {
+        self.with_proof(ConditionProof::AfterBlock)
+    }
+
+    pub fn hash_preimage(&mut self, pre_image: AnyHash) -> &mut Self {
+        self.with_proof(ConditionProof::HashPreimage(pre_image))
+    }
+
+    pub fn signed_by(&mut self, signature: SignatureProof) -> &mut Self {
+        self.with_proof(ConditionProof::SignedBy(signature))
+    }
+
+    pub fn and(&mut self, lhs: ConditionProof, rhs: ConditionProof) -> &mut Self {
+        self.with_proof(ConditionProof::And(Box::new(lhs), Box::new(rhs)))
+    }
+
+    pub fn or(&mut self, lhs: ConditionProof, rhs: ConditionProof) -> &mut Self {
+        self.with_proof(ConditionProof::Or(Box::new(lhs), Box::new(rhs)))
+    }
+
+    /// Checks the collected proof against `condition` with `Condition::evaluate` before writing
+    /// it to the transaction, so a proof that doesn't actually satisfy the contract's predicate
+    /// can't leave the builder pipeline.
+    pub fn generate(self, condition: &Condition, block_height: u32) -> Option<Transaction> {
+        let proof = self.proof?;
+        let mut tx = self.transaction;
+
+        if !condition.evaluate(&proof, tx.serialize_content().as_slice(), block_height) {
+            return None;
+        }
+
+        tx.proof = proof.serialize_to_vec();
+        Some(tx)
+    }
+}
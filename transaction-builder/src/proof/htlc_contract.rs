@@ -1,8 +1,11 @@
-use beserial::{Serialize, SerializingError, WriteBytesExt};
-use hash::{Blake2bHash, Sha256Hash};
+use beserial::{Deserialize, ReadBytesExt, Serialize, SerializingError, WriteBytesExt};
+use failure::Fail;
+use hash::{Blake2bHash, Blake2bHasher, Hasher, Sha256Hash, Sha256Hasher};
 use keys::KeyPair;
+use primitives::networks::NetworkId;
 use transaction::{SignatureProof, Transaction};
 use transaction::account::htlc_contract::{AnyHash, HashAlgorithm, ProofType};
+use utils::key_rng::SecureGenerate;
 
 pub enum HtlcProof {
     RegularTransfer {
@@ -71,9 +74,32 @@ impl Serialize for HtlcProof {
     }
 }
 
+impl Deserialize for HtlcProof {
+    fn deserialize<R: ReadBytesExt>(reader: &mut R) -> Result<Self, SerializingError> {
+        let proof_type: ProofType = Deserialize::deserialize(reader)?;
+        Ok(match proof_type {
+            ProofType::RegularTransfer => HtlcProof::RegularTransfer {
+                hash_algorithm: Deserialize::deserialize(reader)?,
+                hash_depth: Deserialize::deserialize(reader)?,
+                hash_root: Deserialize::deserialize(reader)?,
+                pre_image: Deserialize::deserialize(reader)?,
+                recipient_signature: Deserialize::deserialize(reader)?,
+            },
+            ProofType::EarlyResolve => HtlcProof::EarlyResolve {
+                recipient_signature: Deserialize::deserialize(reader)?,
+                sender_signature: Deserialize::deserialize(reader)?,
+            },
+            ProofType::TimeoutResolve => HtlcProof::TimeoutResolve {
+                signature: Deserialize::deserialize(reader)?,
+            },
+        })
+    }
+}
+
 pub struct HtlcProofBuilder {
     pub transaction: Transaction,
     proof: Option<HtlcProof>,
+    network_id: Option<NetworkId>,
 }
 
 impl HtlcProofBuilder {
@@ -81,9 +107,18 @@ impl HtlcProofBuilder {
         HtlcProofBuilder {
             transaction,
             proof: None,
+            network_id: None,
         }
     }
 
+    /// Tells `generate` to reject the transaction unless it is bound to `network_id`. Unset by
+    /// default, in which case `generate` doesn't examine the transaction's network at all; see
+    /// `BasicProofBuilder::with_network_id` for why a caller would want to set this.
+    pub fn with_network_id(&mut self, network_id: NetworkId) -> &mut Self {
+        self.network_id = Some(network_id);
+        self
+    }
+
     pub fn signature_with_key_pair(&self, key_pair: &KeyPair) -> SignatureProof {
         let signature = key_pair.sign(self.transaction.serialize_content().as_slice());
         SignatureProof::from(key_pair.public, signature)
@@ -128,8 +163,148 @@ impl HtlcProofBuilder {
     }
 
     pub fn generate(self) -> Option<Transaction> {
+        if let Some(network_id) = self.network_id {
+            if self.transaction.network_id != network_id {
+                return None;
+            }
+        }
+
         let mut tx = self.transaction;
         tx.proof = self.proof?.serialize_to_vec();
         Some(tx)
     }
+
+    /// Parses `tx`'s `proof` and, if it is a `RegularTransfer`, returns the pre-image it reveals.
+    /// Once a `RegularTransfer` redeem hits either chain of a cross-chain atomic swap, this is
+    /// how the other side recovers the shared secret to redeem its own leg.
+    pub fn extract_pre_image(tx: &Transaction) -> Option<AnyHash> {
+        match HtlcProof::deserialize_from_vec(&tx.proof).ok()? {
+            HtlcProof::RegularTransfer { pre_image, .. } => Some(pre_image),
+            _ => None,
+        }
+    }
+}
+
+/// Generates the secret/hashlock pair for one leg of a cross-chain atomic swap (as in the
+/// xmr-btc-swap design): a random secret is drawn, and `hash_algorithm` is applied to it
+/// `hash_count` times to produce `hash_root`. The caller publishes `hash_root` (and
+/// `hash_algorithm`/`hash_count`) to set up the matching HTLC on the counterpart chain, keeps
+/// `secret` private until redeem time, and later recovers the counterparty's secret from their
+/// on-chain `RegularTransfer` redeem via `HtlcProofBuilder::extract_pre_image`.
+pub struct AtomicSwapBuilder {
+    pub hash_algorithm: HashAlgorithm,
+    pub hash_count: u8,
+    pub hash_root: AnyHash,
+    secret: AnyHash,
+}
+
+impl AtomicSwapBuilder {
+    pub fn generate(hash_algorithm: HashAlgorithm, hash_count: u8) -> Self {
+        let secret = AnyHash::generate_default_csprng();
+        let hash_root = Self::hash_secret(hash_algorithm, hash_count, &secret);
+        AtomicSwapBuilder {
+            hash_algorithm,
+            hash_count,
+            hash_root,
+            secret,
+        }
+    }
+
+    pub fn secret(&self) -> &AnyHash {
+        &self.secret
+    }
+
+    fn hash_secret(hash_algorithm: HashAlgorithm, hash_count: u8, secret: &AnyHash) -> AnyHash {
+        let mut digest: [u8; 32] = secret.clone().into();
+        for _ in 0..hash_count {
+            digest = match hash_algorithm {
+                HashAlgorithm::Blake2b => Blake2bHasher::new().digest(&digest).into(),
+                HashAlgorithm::Sha256 => Sha256Hasher::new().digest(&digest).into(),
+            };
+        }
+        digest.into()
+    }
+}
+
+#[derive(Clone, Debug, Fail)]
+pub enum PartialHtlcProofError {
+    #[fail(display = "The two partial proofs are not for the same transaction.")]
+    TransactionMismatch,
+    #[fail(display = "The two partial proofs disagree on the sender signature.")]
+    ConflictingSenderSignature,
+    #[fail(display = "The two partial proofs disagree on the recipient signature.")]
+    ConflictingRecipientSignature,
+}
+
+/// A partially-signed `EarlyResolve` HTLC proof, built up PSBT-style: each party fills in their
+/// own signature slot locally with `add_sender_signature`/`add_recipient_signature` and sends the
+/// result to the other, who merges it in with `combine`. Neither party ever needs to hold both
+/// keys at once.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PartialHtlcProof {
+    pub transaction: Transaction,
+    sender_signature: Option<SignatureProof>,
+    recipient_signature: Option<SignatureProof>,
+}
+
+impl PartialHtlcProof {
+    pub fn new(transaction: Transaction) -> Self {
+        PartialHtlcProof {
+            transaction,
+            sender_signature: None,
+            recipient_signature: None,
+        }
+    }
+
+    pub fn signature_with_key_pair(&self, key_pair: &KeyPair) -> SignatureProof {
+        let signature = key_pair.sign(self.transaction.serialize_content().as_slice());
+        SignatureProof::from(key_pair.public, signature)
+    }
+
+    pub fn add_sender_signature(&mut self, sender_signature: SignatureProof) -> &mut Self {
+        self.sender_signature = Some(sender_signature);
+        self
+    }
+
+    pub fn add_recipient_signature(&mut self, recipient_signature: SignatureProof) -> &mut Self {
+        self.recipient_signature = Some(recipient_signature);
+        self
+    }
+
+    /// Merges the signature slots `other` has filled in into `self`. Fails if the two partials
+    /// were built for different transactions, or if they disagree on a slot both have filled in.
+    pub fn combine(&mut self, other: PartialHtlcProof) -> Result<(), PartialHtlcProofError> {
+        if self.transaction.serialize_content() != other.transaction.serialize_content() {
+            return Err(PartialHtlcProofError::TransactionMismatch);
+        }
+
+        if let Some(sender_signature) = other.sender_signature {
+            match &self.sender_signature {
+                Some(existing) if existing.serialize_to_vec() != sender_signature.serialize_to_vec() =>
+                    return Err(PartialHtlcProofError::ConflictingSenderSignature),
+                _ => self.sender_signature = Some(sender_signature),
+            }
+        }
+
+        if let Some(recipient_signature) = other.recipient_signature {
+            match &self.recipient_signature {
+                Some(existing) if existing.serialize_to_vec() != recipient_signature.serialize_to_vec() =>
+                    return Err(PartialHtlcProofError::ConflictingRecipientSignature),
+                _ => self.recipient_signature = Some(recipient_signature),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Succeeds once both signature slots have been filled in, writing the combined
+    /// `HtlcProof::EarlyResolve` to the transaction.
+    pub fn finalize(self) -> Option<Transaction> {
+        let sender_signature = self.sender_signature?;
+        let recipient_signature = self.recipient_signature?;
+
+        let mut tx = self.transaction;
+        tx.proof = HtlcProof::EarlyResolve { sender_signature, recipient_signature }.serialize_to_vec();
+        Some(tx)
+    }
 }
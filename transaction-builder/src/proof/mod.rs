@@ -1,20 +1,30 @@
+use std::convert::TryFrom;
 use std::io;
 
 use beserial::Serialize;
 use hash::SerializeContent;
-use keys::KeyPair;
+use keys::{KeyPair, PublicKey};
 use primitives::account::AccountType;
+use primitives::networks::NetworkId;
 use transaction::{SignatureProof, Transaction};
 
+use crate::proof::conditional_payment::ConditionalPaymentProofBuilder;
 use crate::proof::htlc_contract::HtlcProofBuilder;
+use crate::proof::multisig::{MultiSigError, MultiSigProofBuilder};
+use crate::proof::musig::MusigProofBuilder;
+use crate::verified::{TransactionVerificationError, UnverifiedTransaction, VerifiedTransaction};
 
 pub mod htlc_contract;
+pub mod musig;
+pub mod multisig;
+pub mod conditional_payment;
 
 pub enum TransactionProofBuilder {
     Basic(BasicProofBuilder),
     Vesting(BasicProofBuilder),
     Htlc(HtlcProofBuilder),
     Staking(BasicProofBuilder),
+    ConditionalPayment(ConditionalPaymentProofBuilder),
 }
 
 impl TransactionProofBuilder {
@@ -32,6 +42,9 @@ impl TransactionProofBuilder {
             AccountType::Staking => TransactionProofBuilder::Staking(
                 BasicProofBuilder::new(transaction)
             ),
+            AccountType::ConditionalPayment => TransactionProofBuilder::ConditionalPayment(
+                ConditionalPaymentProofBuilder::new(transaction)
+            ),
         }
     }
 
@@ -50,6 +63,39 @@ impl TransactionProofBuilder {
             _ => panic!("TransactionProofBuilder was not a HtlcProofBuilder"),
         }
     }
+
+    pub fn unwrap_conditional_payment(self) -> ConditionalPaymentProofBuilder {
+        match self {
+            TransactionProofBuilder::ConditionalPayment(builder) => builder,
+            _ => panic!("TransactionProofBuilder was not a ConditionalPaymentProofBuilder"),
+        }
+    }
+
+    /// Switches to an N-of-N MuSig signing flow for `Basic`/`Vesting`/`Staking` transactions,
+    /// for validators or treasuries that custody a key across several parties. See
+    /// `MusigProofBuilder` for the two-round commit-then-sign protocol.
+    pub fn musig(self, public_keys: Vec<PublicKey>) -> MusigProofBuilder {
+        match self {
+            TransactionProofBuilder::Basic(builder)
+            | TransactionProofBuilder::Vesting(builder)
+            | TransactionProofBuilder::Staking(builder) => MusigProofBuilder::new(builder.transaction, public_keys),
+            TransactionProofBuilder::Htlc(_) => panic!("MuSig signing is not supported for HTLC transactions"),
+            TransactionProofBuilder::ConditionalPayment(_) => panic!("MuSig signing is not supported for ConditionalPayment transactions"),
+        }
+    }
+
+    /// Switches to a k-of-n threshold signing flow for `Staking`/`Vesting` transactions, for
+    /// validators or treasuries whose control is split across several co-signers without the
+    /// interactive commit round `MusigProofBuilder` requires. See `MultiSigProofBuilder`.
+    pub fn multisig(self, public_keys: Vec<PublicKey>, threshold: u16) -> Result<MultiSigProofBuilder, MultiSigError> {
+        match self {
+            TransactionProofBuilder::Staking(builder)
+            | TransactionProofBuilder::Vesting(builder) => MultiSigProofBuilder::new(builder.transaction, public_keys, threshold),
+            TransactionProofBuilder::Basic(_) => panic!("Multisig signing is not supported for Basic transactions"),
+            TransactionProofBuilder::Htlc(_) => panic!("Multisig signing is not supported for HTLC transactions"),
+            TransactionProofBuilder::ConditionalPayment(_) => panic!("Multisig signing is not supported for ConditionalPayment transactions"),
+        }
+    }
 }
 
 impl SerializeContent for TransactionProofBuilder {
@@ -59,6 +105,7 @@ impl SerializeContent for TransactionProofBuilder {
             TransactionProofBuilder::Vesting(builder) => SerializeContent::serialize_content(&builder.transaction, writer),
             TransactionProofBuilder::Htlc(builder) => SerializeContent::serialize_content(&builder.transaction, writer),
             TransactionProofBuilder::Staking(builder) => SerializeContent::serialize_content(&builder.transaction, writer),
+            TransactionProofBuilder::ConditionalPayment(builder) => SerializeContent::serialize_content(&builder.transaction, writer),
         }
     }
 }
@@ -66,6 +113,7 @@ impl SerializeContent for TransactionProofBuilder {
 pub struct BasicProofBuilder {
     pub transaction: Transaction,
     signature: Option<SignatureProof>,
+    network_id: Option<NetworkId>,
 }
 
 impl BasicProofBuilder {
@@ -73,6 +121,7 @@ impl BasicProofBuilder {
         BasicProofBuilder {
             transaction,
             signature: None,
+            network_id: None,
         }
     }
 
@@ -87,9 +136,30 @@ impl BasicProofBuilder {
         self
     }
 
-    pub fn generate(self) -> Option<Transaction> {
+    /// Tells `generate` to reject the transaction unless it is bound to `network_id`. Unset by
+    /// default, in which case `generate` only checks the signature and leaves the transaction's
+    /// own `network_id` unexamined (comparing it to itself would never reject anything); callers
+    /// that verify proofs received from elsewhere, rather than ones they just built themselves,
+    /// should call this with the locally configured network to reject proofs built for a
+    /// different one.
+    pub fn with_network_id(&mut self, network_id: NetworkId) -> &mut Self {
+        self.network_id = Some(network_id);
+        self
+    }
+
+    /// Writes the collected signature to the transaction and checks it before handing the
+    /// result back, so a transaction with an unchecked or mismatched proof can't leave the
+    /// builder pipeline. Also rejects a transaction bound to the wrong network, but only if
+    /// `with_network_id` was actually called; see its doc comment for why there's no useful
+    /// default to fall back to.
+    pub fn generate(self) -> Result<VerifiedTransaction, TransactionVerificationError> {
+        let network_id = self.network_id;
         let mut tx = self.transaction;
-        tx.proof = self.signature?.serialize_to_vec();
-        Some(tx)
+        tx.proof = self.signature.ok_or(TransactionVerificationError::InvalidProof)?.serialize_to_vec();
+        let unverified = UnverifiedTransaction::from(tx);
+        match network_id {
+            Some(network_id) => VerifiedTransaction::try_from_for_network(unverified, network_id),
+            None => VerifiedTransaction::try_from(unverified),
+        }
     }
 }
@@ -0,0 +1,97 @@
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+
+use beserial::Serialize;
+use failure::Fail;
+use keys::{KeyPair, PublicKey, Signature};
+use transaction::{SignatureProof, Transaction};
+
+use crate::verified::{UnverifiedTransaction, VerifiedTransaction};
+
+#[derive(Debug, Fail)]
+pub enum MultiSigError {
+    #[fail(display = "The threshold must be at least 1 and at most the number of signers.")]
+    InvalidThreshold,
+    #[fail(display = "This public key is not part of the signing group.")]
+    UnknownSigner,
+    #[fail(display = "This public key already contributed a signature.")]
+    DuplicateSignature,
+    #[fail(display = "Not enough signatures have been collected yet.")]
+    Incomplete,
+    #[fail(display = "The multisig proof does not verify against the transaction.")]
+    InvalidSignature,
+}
+
+/// Builds a k-of-n threshold `SignatureProof` from an ordered list of `n` public keys, for
+/// validators or treasuries custodied across several co-signers without requiring all of them
+/// to interact like `MusigProofBuilder`'s two-round protocol does. The resulting proof carries
+/// the full key list, the threshold, a bitmap of which keys signed, and the signatures for the
+/// set bits - the same shape as the `MultiEd25519Signature` authenticator in the Diem/Aptos type
+/// layer. The recovered signer address is derived from the full key list and threshold, so it
+/// stays the same no matter which `k` of the `n` keys actually co-sign.
+pub struct MultiSigProofBuilder {
+    pub transaction: Transaction,
+    public_keys: Vec<PublicKey>,
+    threshold: u16,
+    // Keyed by each signer's index into `public_keys`, so `generate` can reconstruct the bitmap.
+    signatures: BTreeMap<u16, Signature>,
+}
+
+impl MultiSigProofBuilder {
+    pub fn new(transaction: Transaction, public_keys: Vec<PublicKey>, threshold: u16) -> Result<Self, MultiSigError> {
+        if threshold == 0 || threshold as usize > public_keys.len() {
+            return Err(MultiSigError::InvalidThreshold);
+        }
+
+        Ok(MultiSigProofBuilder {
+            transaction,
+            public_keys,
+            threshold,
+            signatures: BTreeMap::new(),
+        })
+    }
+
+    pub fn sign_with_key_pair(&self, key_pair: &KeyPair) -> Signature {
+        key_pair.sign(self.transaction.serialize_content().as_slice())
+    }
+
+    /// Contributes `public_key`'s signature over the transaction. Fails if `public_key` is not
+    /// one of the configured signers, or if it already signed.
+    pub fn add_signature(&mut self, public_key: &PublicKey, signature: Signature) -> Result<&mut Self, MultiSigError> {
+        let index = self.public_keys.iter().position(|key| key == public_key)
+            .ok_or(MultiSigError::UnknownSigner)?;
+
+        if self.signatures.insert(index as u16, signature).is_some() {
+            return Err(MultiSigError::DuplicateSignature);
+        }
+
+        Ok(self)
+    }
+
+    /// Whether at least `threshold` distinct keys have signed.
+    pub fn is_complete(&self) -> bool {
+        self.signatures.len() as u16 >= self.threshold
+    }
+
+    /// Assembles the bitmap and concatenated signatures for every signer that has contributed,
+    /// writes the resulting threshold `SignatureProof` to the transaction, and checks it before
+    /// handing back a `VerifiedTransaction`.
+    pub fn generate(self) -> Result<VerifiedTransaction, MultiSigError> {
+        if !self.is_complete() {
+            return Err(MultiSigError::Incomplete);
+        }
+
+        let mut bitmap = vec![false; self.public_keys.len()];
+        let mut signatures = Vec::with_capacity(self.signatures.len());
+        for (&index, signature) in self.signatures.iter() {
+            bitmap[index as usize] = true;
+            signatures.push(signature.clone());
+        }
+
+        let signature_proof = SignatureProof::from_multisig(self.public_keys.clone(), self.threshold, bitmap, signatures);
+
+        let mut tx = self.transaction;
+        tx.proof = signature_proof.serialize_to_vec();
+        VerifiedTransaction::try_from(UnverifiedTransaction::from(tx)).map_err(|_| MultiSigError::InvalidSignature)
+    }
+}
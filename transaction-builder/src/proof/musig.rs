@@ -0,0 +1,224 @@
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+
+use beserial::Serialize;
+use failure::Fail;
+use hash::{Blake2bHasher, Hasher};
+use keys::PublicKey;
+use transaction::{SignatureProof, Transaction};
+
+use crate::verified::{UnverifiedTransaction, VerifiedTransaction};
+
+/// A 256-bit little-endian Ed25519 scalar, reduced modulo the group order `L`.
+pub type Scalar = [u8; 32];
+
+/// `H(R_i)`, a signer's round-1 commitment to their nonce point. Collecting these before any
+/// `R_i` is revealed prevents a Wagner-style rogue-nonce attack on the aggregate signature.
+pub type NonceCommitment = [u8; 32];
+
+/// The Ed25519 group order `L = 2^252 + 27742317777372353535851937790883648493`, little-endian.
+const GROUP_ORDER_L: Scalar = [
+    0xed, 0xd3, 0xf5, 0x5c, 0x1a, 0x63, 0x12, 0x58,
+    0xd6, 0x9c, 0xf7, 0xa2, 0xde, 0xf9, 0xde, 0x14,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x10,
+];
+
+fn scalar_less_than(a: &Scalar, b: &Scalar) -> bool {
+    for i in (0..32).rev() {
+        if a[i] != b[i] {
+            return a[i] < b[i];
+        }
+    }
+    false
+}
+
+fn scalar_sub(a: &Scalar, b: &Scalar) -> Scalar {
+    let mut result = [0u8; 32];
+    let mut borrow = 0i16;
+    for i in 0..32 {
+        let diff = a[i] as i16 - b[i] as i16 - borrow;
+        if diff < 0 {
+            result[i] = (diff + 256) as u8;
+            borrow = 1;
+        } else {
+            result[i] = diff as u8;
+            borrow = 0;
+        }
+    }
+    result
+}
+
+/// Adds two scalars modulo `L`. Each input is assumed to already be reduced.
+fn scalar_add_mod_l(a: &Scalar, b: &Scalar) -> Scalar {
+    let mut sum = [0u8; 32];
+    let mut carry = 0u16;
+    for i in 0..32 {
+        let s = a[i] as u16 + b[i] as u16 + carry;
+        sum[i] = (s & 0xff) as u8;
+        carry = s >> 8;
+    }
+    if carry > 0 || !scalar_less_than(&sum, &GROUP_ORDER_L) {
+        scalar_sub(&sum, &GROUP_ORDER_L)
+    } else {
+        sum
+    }
+}
+
+/// Reduces a 256-bit Blake2b digest to a scalar modulo `L`, for deriving aggregation
+/// coefficients and Fiat-Shamir challenges from arbitrary-length messages.
+fn hash_to_scalar(bytes: &[u8]) -> Scalar {
+    let digest = Blake2bHasher::new().digest(bytes);
+    let mut scalar: Scalar = digest.into();
+    while !scalar_less_than(&scalar, &GROUP_ORDER_L) {
+        scalar = scalar_sub(&scalar, &GROUP_ORDER_L);
+    }
+    scalar
+}
+
+/// MuSig public-key aggregation coefficient `H(L, X_i)`, where `L` is the hash of the sorted
+/// list of participating public keys.
+fn aggregation_coefficient(key_list_hash: &[u8; 32], public_key: &PublicKey) -> Scalar {
+    let mut data = key_list_hash.to_vec();
+    data.extend_from_slice(public_key.serialize_to_vec().as_slice());
+    hash_to_scalar(&data)
+}
+
+#[derive(Debug, Fail)]
+pub enum MusigError {
+    #[fail(display = "This public key is not part of the signing group.")]
+    UnknownSigner,
+    #[fail(display = "This public key already published a nonce commitment.")]
+    DuplicateNonceCommitment,
+    #[fail(display = "Not every signer has committed to a nonce yet.")]
+    MissingNonceCommitments,
+    #[fail(display = "The revealed nonce does not match the signer's round-1 commitment.")]
+    NonceCommitmentMismatch,
+    #[fail(display = "Not enough partial signatures have been collected yet.")]
+    Incomplete,
+    #[fail(display = "The aggregated MuSig signature does not verify against the transaction.")]
+    InvalidSignature,
+}
+
+/// Builds a single aggregated `SignatureProof` from a group of N Ed25519 keys, all N of which
+/// must sign (N-of-N; a subset of the keys cannot produce the proof), so that
+/// the on-chain verifier (and `StakingContract::get_signer`) sees an ordinary signature.
+///
+/// This follows the two-round MuSig protocol: every signer first commits to a nonce
+/// (`commit_nonce`, `H(R_i)`) to rule out rogue-nonce attacks, then reveals `R_i` together
+/// with their partial signature scalar `s_i = r_i + H(X,R,m)·H(L,X_i)·x_i`
+/// (`add_partial_signature`). Once every signer has contributed, `generate()` sums the
+/// partial signatures into `(R, s)` and writes the resulting proof to the transaction.
+pub struct MusigProofBuilder {
+    pub transaction: Transaction,
+    public_keys: Vec<PublicKey>,
+    key_list_hash: [u8; 32],
+    nonce_commitments: BTreeMap<PublicKey, NonceCommitment>,
+    nonce_reveals: BTreeMap<PublicKey, PublicKey>,
+    partial_signatures: BTreeMap<PublicKey, Scalar>,
+}
+
+impl MusigProofBuilder {
+    /// Creates a new signing session for `public_keys` over `transaction`. The keys are
+    /// sorted so that the resulting aggregate public key (and thus the recovered signer
+    /// address) is independent of the order they were supplied in.
+    pub fn new(transaction: Transaction, mut public_keys: Vec<PublicKey>) -> Self {
+        public_keys.sort_by_key(|key| key.serialize_to_vec());
+        let key_list_hash = Blake2bHasher::new().digest(
+            &public_keys.iter().flat_map(|key| key.serialize_to_vec()).collect::<Vec<u8>>()
+        ).into();
+
+        MusigProofBuilder {
+            transaction,
+            public_keys,
+            key_list_hash,
+            nonce_commitments: BTreeMap::new(),
+            nonce_reveals: BTreeMap::new(),
+            partial_signatures: BTreeMap::new(),
+        }
+    }
+
+    fn message(&self) -> Vec<u8> {
+        use hash::SerializeContent;
+        self.transaction.serialize_content()
+    }
+
+    /// Round 1: publishes a signer's commitment `H(R_i)` to their (not yet revealed) nonce.
+    pub fn commit_nonce(&mut self, public_key: PublicKey, commitment: NonceCommitment) -> Result<&mut Self, MusigError> {
+        if !self.public_keys.contains(&public_key) {
+            return Err(MusigError::UnknownSigner);
+        }
+        if self.nonce_commitments.insert(public_key, commitment).is_some() {
+            return Err(MusigError::DuplicateNonceCommitment);
+        }
+        Ok(self)
+    }
+
+    /// Round 2: reveals a signer's nonce point `R_i` together with their partial signature
+    /// scalar `s_i`. Fails unless every signer has already committed to a nonce, and unless
+    /// `R_i` matches the commitment that signer published in round 1.
+    pub fn add_partial_signature(&mut self, public_key: PublicKey, nonce: PublicKey, partial_signature: Scalar) -> Result<&mut Self, MusigError> {
+        if !self.public_keys.contains(&public_key) {
+            return Err(MusigError::UnknownSigner);
+        }
+        if self.nonce_commitments.len() != self.public_keys.len() {
+            return Err(MusigError::MissingNonceCommitments);
+        }
+
+        let expected_commitment = self.nonce_commitments.get(&public_key).ok_or(MusigError::UnknownSigner)?;
+        let actual_commitment: NonceCommitment = Blake2bHasher::new().digest(nonce.serialize_to_vec().as_slice()).into();
+        if &actual_commitment != expected_commitment {
+            return Err(MusigError::NonceCommitmentMismatch);
+        }
+
+        self.nonce_reveals.insert(public_key, nonce);
+        self.partial_signatures.insert(public_key, partial_signature);
+        Ok(self)
+    }
+
+    /// The MuSig aggregation coefficient `H(L, X_i)` for `public_key`, needed by each signer
+    /// to compute their partial signature.
+    pub fn coefficient_for(&self, public_key: &PublicKey) -> Scalar {
+        aggregation_coefficient(&self.key_list_hash, public_key)
+    }
+
+    /// Whether every participant has contributed a partial signature.
+    pub fn is_complete(&self) -> bool {
+        self.partial_signatures.len() == self.public_keys.len()
+    }
+
+    /// Sums the collected partial signatures into the final `(R, s)` pair, writes the
+    /// resulting `SignatureProof` to the transaction, and checks it before handing back a
+    /// `VerifiedTransaction`.
+    pub fn generate(self) -> Result<VerifiedTransaction, MusigError> {
+        if !self.is_complete() {
+            return Err(MusigError::Incomplete);
+        }
+
+        let s = self.partial_signatures.values()
+            .fold([0u8; 32], |acc, partial| scalar_add_mod_l(&acc, partial));
+
+        let aggregate_nonce = self.nonce_reveals.values()
+            .fold(None, |acc: Option<PublicKey>, nonce| Some(match acc {
+                Some(sum) => sum.combine(nonce),
+                None => nonce.clone(),
+            }))
+            .expect("at least one signer");
+
+        let aggregate_public_key = self.public_keys.iter()
+            .fold(None, |acc: Option<PublicKey>, key| {
+                let weighted = key.scalar_mul(&aggregation_coefficient(&self.key_list_hash, key));
+                Some(match acc {
+                    Some(sum) => sum.combine(&weighted),
+                    None => weighted,
+                })
+            })
+            .expect("at least one signer");
+
+        let signature = SignatureProof::from_musig(aggregate_public_key, aggregate_nonce, s);
+
+        let mut tx = self.transaction;
+        tx.proof = signature.serialize_to_vec();
+        VerifiedTransaction::try_from(UnverifiedTransaction::from(tx)).map_err(|_| MusigError::InvalidSignature)
+    }
+}
@@ -0,0 +1,224 @@
+use beserial::{Deserialize, ReadBytesExt, Serialize, SerializingError, WriteBytesExt};
+use hash::{Blake2bHash, Sha256Hash};
+use keys::Address;
+use transaction::account::htlc_contract::{AnyHash, HashAlgorithm};
+
+use crate::recipient::Recipient;
+
+/// Maximum nesting depth of a `Condition` expression tree. Bounds the recursion (and thus the
+/// work) evaluating or decoding a `Condition` does for a single proof, so a `ConditionalPayment`
+/// can't be created with - or handed - a predicate that's prohibitively expensive to verify.
+pub const MAX_CONDITION_DEPTH: u8 = 8;
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, Eq, PartialEq)]
+enum ConditionType {
+    AfterBlock,
+    HashPreimage,
+    SignedBy,
+    And,
+    Or,
+}
+
+/// A boolean expression over primitive release conditions, generalizing `HtlcContract`'s single
+/// hash-lock-and-timeout into an arbitrary predicate tree. Funds are released to `recipient` once
+/// a `ConditionProof` demonstrates the root `Condition` of the `ConditionalPaymentCreationData`
+/// it was created with evaluates to `true`. Serialized as a discriminant tag followed by its
+/// fields, so the encoding - and thus the contract's address and hash - is canonical.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Condition {
+    AfterBlock(u32),
+    HashPreimage {
+        hash_algorithm: HashAlgorithm,
+        hash_root: AnyHash,
+        hash_count: u8,
+    },
+    SignedBy(Address),
+    And(Box<Condition>, Box<Condition>),
+    Or(Box<Condition>, Box<Condition>),
+}
+
+impl Condition {
+    /// Number of nodes on the deepest root-to-leaf path, counting a leaf as depth 1.
+    pub fn depth(&self) -> u8 {
+        match self {
+            Condition::AfterBlock(_) | Condition::HashPreimage { .. } | Condition::SignedBy(_) => 1,
+            Condition::And(lhs, rhs) | Condition::Or(lhs, rhs) => 1 + lhs.depth().max(rhs.depth()),
+        }
+    }
+
+    pub fn and(self, other: Condition) -> Condition {
+        Condition::And(Box::new(self), Box::new(other))
+    }
+
+    pub fn or(self, other: Condition) -> Condition {
+        Condition::Or(Box::new(self), Box::new(other))
+    }
+
+    /// Decodes a `Condition`, rejecting a tree deeper than `max_depth` rather than recursing
+    /// arbitrarily far into attacker-controlled wire data.
+    fn deserialize_bounded<R: ReadBytesExt>(reader: &mut R, max_depth: u8) -> Result<Self, SerializingError> {
+        if max_depth == 0 {
+            return Err(SerializingError::InvalidValue);
+        }
+        let condition_type: ConditionType = Deserialize::deserialize(reader)?;
+        Ok(match condition_type {
+            ConditionType::AfterBlock => Condition::AfterBlock(Deserialize::deserialize(reader)?),
+            ConditionType::HashPreimage => Condition::HashPreimage {
+                hash_algorithm: Deserialize::deserialize(reader)?,
+                hash_root: Deserialize::deserialize(reader)?,
+                hash_count: Deserialize::deserialize(reader)?,
+            },
+            ConditionType::SignedBy => Condition::SignedBy(Deserialize::deserialize(reader)?),
+            ConditionType::And | ConditionType::Or => {
+                let child_depth = max_depth.checked_sub(1).ok_or(SerializingError::InvalidValue)?;
+                let lhs = Box::new(Condition::deserialize_bounded(reader, child_depth)?);
+                let rhs = Box::new(Condition::deserialize_bounded(reader, child_depth)?);
+                if condition_type == ConditionType::And {
+                    Condition::And(lhs, rhs)
+                } else {
+                    Condition::Or(lhs, rhs)
+                }
+            },
+        })
+    }
+}
+
+impl Serialize for Condition {
+    fn serialize<W: WriteBytesExt>(&self, writer: &mut W) -> Result<usize, SerializingError> {
+        let mut size = 0;
+        match self {
+            Condition::AfterBlock(height) => {
+                size += ConditionType::AfterBlock.serialize(writer)?;
+                size += height.serialize(writer)?;
+            },
+            Condition::HashPreimage { hash_algorithm, hash_root, hash_count } => {
+                size += ConditionType::HashPreimage.serialize(writer)?;
+                size += hash_algorithm.serialize(writer)?;
+                size += hash_root.serialize(writer)?;
+                size += hash_count.serialize(writer)?;
+            },
+            Condition::SignedBy(address) => {
+                size += ConditionType::SignedBy.serialize(writer)?;
+                size += address.serialize(writer)?;
+            },
+            Condition::And(lhs, rhs) => {
+                size += ConditionType::And.serialize(writer)?;
+                size += lhs.serialize(writer)?;
+                size += rhs.serialize(writer)?;
+            },
+            Condition::Or(lhs, rhs) => {
+                size += ConditionType::Or.serialize(writer)?;
+                size += lhs.serialize(writer)?;
+                size += rhs.serialize(writer)?;
+            },
+        }
+        Ok(size)
+    }
+
+    fn serialized_size(&self) -> usize {
+        let mut size = match self {
+            Condition::AfterBlock(_) => ConditionType::AfterBlock.serialized_size(),
+            Condition::HashPreimage { .. } => ConditionType::HashPreimage.serialized_size(),
+            Condition::SignedBy(_) => ConditionType::SignedBy.serialized_size(),
+            Condition::And(..) => ConditionType::And.serialized_size(),
+            Condition::Or(..) => ConditionType::Or.serialized_size(),
+        };
+        match self {
+            Condition::AfterBlock(height) => size += height.serialized_size(),
+            Condition::HashPreimage { hash_algorithm, hash_root, hash_count } => {
+                size += hash_algorithm.serialized_size();
+                size += hash_root.serialized_size();
+                size += hash_count.serialized_size();
+            },
+            Condition::SignedBy(address) => size += address.serialized_size(),
+            Condition::And(lhs, rhs) | Condition::Or(lhs, rhs) => {
+                size += lhs.serialized_size();
+                size += rhs.serialized_size();
+            },
+        }
+        size
+    }
+}
+
+impl Deserialize for Condition {
+    fn deserialize<R: ReadBytesExt>(reader: &mut R) -> Result<Self, SerializingError> {
+        Condition::deserialize_bounded(reader, MAX_CONDITION_DEPTH)
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub struct ConditionalPaymentCreationData {
+    pub sender: Address,
+    pub recipient: Address,
+    pub condition: Condition,
+}
+
+pub struct ConditionalPaymentRecipientBuilder {
+    sender: Address,
+    recipient: Address,
+    condition: Option<Condition>,
+}
+
+impl ConditionalPaymentRecipientBuilder {
+    pub fn new(sender: Address, recipient: Address) -> Self {
+        ConditionalPaymentRecipientBuilder {
+            sender,
+            recipient,
+            condition: None,
+        }
+    }
+
+    pub fn with_condition(&mut self, condition: Condition) -> &mut Self {
+        self.condition = Some(condition);
+        self
+    }
+
+    pub fn with_after_block(&mut self, block_height: u32) -> &mut Self {
+        self.with_condition(Condition::AfterBlock(block_height))
+    }
+
+    pub fn with_signed_by(&mut self, address: Address) -> &mut Self {
+        self.with_condition(Condition::SignedBy(address))
+    }
+
+    pub fn with_sha256_preimage(&mut self, hash_root: Sha256Hash, hash_count: u8) -> &mut Self {
+        let hash_root: [u8; 32] = hash_root.into();
+        self.with_condition(Condition::HashPreimage {
+            hash_algorithm: HashAlgorithm::Sha256,
+            hash_root: hash_root.into(),
+            hash_count,
+        })
+    }
+
+    pub fn with_blake2b_preimage(&mut self, hash_root: Blake2bHash, hash_count: u8) -> &mut Self {
+        let hash_root: [u8; 32] = hash_root.into();
+        self.with_condition(Condition::HashPreimage {
+            hash_algorithm: HashAlgorithm::Blake2b,
+            hash_root: hash_root.into(),
+            hash_count,
+        })
+    }
+
+    /// Builds the `ConditionalPayment` recipient, or `None` if no condition was set or the
+    /// configured `Condition` exceeds `MAX_CONDITION_DEPTH`.
+    pub fn generate(self) -> Option<Recipient> {
+        let condition = self.condition?;
+        if condition.depth() > MAX_CONDITION_DEPTH {
+            return None;
+        }
+
+        Some(Recipient::ConditionalPaymentCreation {
+            data: ConditionalPaymentCreationData {
+                sender: self.sender,
+                recipient: self.recipient,
+                condition,
+            },
+        })
+    }
+}
+
+impl From<ConditionalPaymentRecipientBuilder> for Option<Recipient> {
+    fn from(builder: ConditionalPaymentRecipientBuilder) -> Self {
+        builder.generate()
+    }
+}
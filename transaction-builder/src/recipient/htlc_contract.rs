@@ -1,9 +1,13 @@
+use beserial::Serialize;
 use hash::{Blake2bHash, Sha256Hash};
 use keys::Address;
+use nimiq_account::AccountType;
+use primitives::coin::Coin;
+use primitives::networks::NetworkId;
 use transaction::account::htlc_contract::{AnyHash, HashAlgorithm};
 use transaction::account::htlc_contract::CreationTransactionData as HtlcCreationData;
 
-use crate::recipient::Recipient;
+use crate::recipient::{contract_creation_address, Recipient};
 
 #[derive(Default)]
 pub struct HtlcRecipientBuilder {
@@ -62,6 +66,13 @@ impl HtlcRecipientBuilder {
         self
     }
 
+    /// Derives the address this contract will occupy once its creation transaction is mined,
+    /// without having to build and mine that transaction first. See
+    /// `Recipient::contract_address`.
+    pub fn contract_address(&self, creator: &Address, sender_type: AccountType, value: Coin, fee: Coin, validity_start_height: u32, network_id: NetworkId) -> Address {
+        contract_creation_address(&self.contract_creation_data.serialize_to_vec(), creator, sender_type, AccountType::HTLC, value, fee, validity_start_height, network_id)
+    }
+
     pub fn generate(self) -> Recipient {
         Recipient::HtlcCreation {
             data: self.into()
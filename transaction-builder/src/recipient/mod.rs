@@ -1,9 +1,14 @@
 use beserial::Serialize;
+use hash::{Blake2bHasher, Hasher};
 use keys::Address;
 use nimiq_account::AccountType;
+use primitives::coin::Coin;
+use primitives::networks::NetworkId;
 use transaction::account::htlc_contract::CreationTransactionData as HtlcCreationData;
 use transaction::account::vesting_contract::CreationTransactionData as VestingCreationData;
+use transaction::Transaction;
 
+use crate::recipient::conditional_payment::{ConditionalPaymentCreationData, ConditionalPaymentRecipientBuilder};
 use crate::recipient::htlc_contract::HtlcRecipientBuilder;
 use crate::recipient::staking_contract::{StakingRecipientBuilder, StakingTransaction};
 use crate::recipient::vesting_contract::VestingRecipientBuilder;
@@ -11,6 +16,7 @@ use crate::recipient::vesting_contract::VestingRecipientBuilder;
 pub mod vesting_contract;
 pub mod htlc_contract;
 pub mod staking_contract;
+pub mod conditional_payment;
 
 pub enum Recipient {
     Basic {
@@ -26,6 +32,9 @@ pub enum Recipient {
         address: Address,
         data: StakingTransaction,
     },
+    ConditionalPaymentCreation {
+        data: ConditionalPaymentCreationData,
+    },
 }
 
 impl Recipient {
@@ -47,6 +56,10 @@ impl Recipient {
         StakingRecipientBuilder::new(staking_contract)
     }
 
+    pub fn new_conditional_payment_builder(sender: Address, recipient: Address) -> ConditionalPaymentRecipientBuilder {
+        ConditionalPaymentRecipientBuilder::new(sender, recipient)
+    }
+
     pub fn is_creation(&self) -> bool {
         match self {
             Recipient::Basic { .. } | Recipient::Staking { .. } => false,
@@ -60,6 +73,7 @@ impl Recipient {
             Recipient::HtlcCreation { .. } => AccountType::HTLC,
             Recipient::VestingCreation { .. } => AccountType::Vesting,
             Recipient::Staking { .. } => AccountType::Staking,
+            Recipient::ConditionalPaymentCreation { .. } => AccountType::ConditionalPayment,
         }
     }
 
@@ -77,6 +91,7 @@ impl Recipient {
             Recipient::HtlcCreation { data } => data.serialize_to_vec(),
             Recipient::VestingCreation { data } => data.serialize_to_vec(),
             Recipient::Staking { data, .. } => data.serialize_to_vec(),
+            Recipient::ConditionalPaymentCreation { data } => data.serialize_to_vec(),
         }
     }
 
@@ -92,4 +107,34 @@ impl Recipient {
             _ => true,
         }
     }
+
+    /// Derives the address this recipient's creation transaction will occupy, without having to
+    /// build and mine that transaction first. Mirrors Serai's `Deployer`/CREATE-style
+    /// deterministic addressing: the address is the same canonical content hash the chain itself
+    /// derives the new contract's account address from, so a caller can reference a not-yet-mined
+    /// contract from a second transaction built in the same batch.
+    pub fn contract_address(&self, creator: &Address, sender_type: AccountType, value: Coin, fee: Coin, validity_start_height: u32, network_id: NetworkId) -> Address {
+        contract_creation_address(&self.data(), creator, sender_type, self.account_type(), value, fee, validity_start_height, network_id)
+    }
+}
+
+/// Shared by `Recipient::contract_address` and the individual recipient builders: replicates
+/// `Transaction::new_contract_creation`'s canonical encoding for the given creation `data` and
+/// hashes it the same way the chain derives a contract's account address.
+pub(crate) fn contract_creation_address(data: &[u8], creator: &Address, sender_type: AccountType, account_type: AccountType, value: Coin, fee: Coin, validity_start_height: u32, network_id: NetworkId) -> Address {
+    let tx = Transaction::new_contract_creation(
+        data.to_vec(),
+        creator.clone(),
+        sender_type,
+        account_type,
+        value,
+        fee,
+        validity_start_height,
+        network_id,
+    );
+
+    let digest: [u8; 32] = Blake2bHasher::new().digest(&tx.serialize_content()).into();
+    let mut address_bytes = [0u8; 20];
+    address_bytes.copy_from_slice(&digest[..20]);
+    Address::from(address_bytes)
 }
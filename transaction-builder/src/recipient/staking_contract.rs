@@ -1,15 +1,19 @@
+use account::staking_contract::StakingContract;
 use beserial::{Serialize, SerializingError, WriteBytesExt};
-use bls::bls12_381::{CompressedSignature, KeyPair};
+use bls::bls12_381::{CompressedPublicKey, CompressedSignature, KeyPair};
 use keys::Address;
-use transaction::account::staking_contract::{StakingTransactionData, StakingTransactionType};
+use primitives::coin::Coin;
+use transaction::account::staking_contract::{StakingTransactionData, StakingTransactionType, UpdateValidatorKeyData};
 use utils::key_rng::SecureGenerate;
 
 use crate::recipient::Recipient;
+use crate::TransactionBuilderError;
 
 pub enum StakingTransaction {
     Stake(StakingTransactionData),
     Retire,
     Unpark,
+    UpdateValidatorKey(UpdateValidatorKeyData),
 }
 
 impl StakingTransaction {
@@ -19,6 +23,50 @@ impl StakingTransaction {
             _ => true,
         }
     }
+
+    /// Single source of truth for whether this staking operation would be accepted by
+    /// `StakingContract`'s `check_incoming_transaction`/`check_outgoing_transaction`, shared by
+    /// `TransactionBuilder::validate_against` and `StakingRecipientBuilder::validate_against` so
+    /// the two can't independently drift on what counts as a valid staking transaction.
+    /// `unpark_validator_key` must be supplied for `Unpark`, since the validator being unparked is
+    /// not itself part of the wire data.
+    pub(crate) fn validate_against(&self, contract: &StakingContract, sender: &Address, value: Coin, fee: Coin, unpark_validator_key: Option<&CompressedPublicKey>) -> Result<(), TransactionBuilderError> {
+        match self {
+            StakingTransaction::Stake(data) => {
+                // Mirrors `commit_incoming_transaction`: a new validator must prove possession of
+                // its own BLS key, but a top-up of one that's already registered doesn't, since an
+                // arbitrary delegating `staker_address` has no way to produce that proof.
+                if contract.get_validator(&data.validator_key).is_none()
+                    && !data.validator_key.verify(&StakingContract::proof_of_knowledge_message(&data.validator_key), &data.proof_of_knowledge)
+                {
+                    return Err(TransactionBuilderError::InvalidProofOfKnowledge);
+                }
+            },
+            StakingTransaction::Retire => {
+                let active_stake = contract.get_active_balance(sender);
+                if active_stake < value {
+                    return Err(TransactionBuilderError::InsufficientActiveStake);
+                }
+            },
+            StakingTransaction::Unpark => {
+                let validator_key = unpark_validator_key.ok_or(TransactionBuilderError::UnknownValidator)?;
+
+                if !contract.current_epoch_parking.contains(validator_key) && !contract.previous_epoch_parking.contains(validator_key) {
+                    return Err(TransactionBuilderError::ValidatorNotParked);
+                }
+
+                let parked_balance = contract.get_validator(validator_key)
+                    .map(|validator| validator.lock().balance)
+                    .ok_or(TransactionBuilderError::UnknownValidator)?;
+                if value + fee != parked_balance {
+                    return Err(TransactionBuilderError::InvalidUnparkValue);
+                }
+            },
+            StakingTransaction::UpdateValidatorKey(_) => {},
+        }
+
+        Ok(())
+    }
 }
 
 impl Serialize for StakingTransaction {
@@ -27,6 +75,7 @@ impl Serialize for StakingTransaction {
             StakingTransaction::Stake(data) => data.serialize(writer),
             StakingTransaction::Retire => StakingTransactionType::Retire.serialize(writer),
             StakingTransaction::Unpark => StakingTransactionType::Unpark.serialize(writer),
+            StakingTransaction::UpdateValidatorKey(data) => data.serialize(writer),
         }
     }
 
@@ -35,6 +84,7 @@ impl Serialize for StakingTransaction {
             StakingTransaction::Stake(data) => data.serialized_size(),
             StakingTransaction::Retire => StakingTransactionType::Retire.serialized_size(),
             StakingTransaction::Unpark => StakingTransactionType::Unpark.serialized_size(),
+            StakingTransaction::UpdateValidatorKey(data) => data.serialized_size(),
         }
     }
 }
@@ -77,8 +127,40 @@ impl StakingRecipientBuilder {
         self
     }
 
+    /// Rotates a validator's BLS key from `old_validator_key` to the key held by `new_key_pair`,
+    /// without unstaking. `new_key_pair` must sign a proof of knowledge of its own secret key.
+    pub fn update_validator_key(&mut self, old_validator_key: CompressedPublicKey, new_key_pair: &KeyPair) -> &mut Self {
+        self.staking_data = Some(StakingTransaction::UpdateValidatorKey(UpdateValidatorKeyData {
+            old_validator_key,
+            new_validator_key: new_key_pair.public.compress(),
+            proof_of_knowledge: StakingRecipientBuilder::generate_proof_of_knowledge(&new_key_pair),
+        }));
+        self
+    }
+
     pub fn generate_proof_of_knowledge(key_pair: &KeyPair) -> CompressedSignature {
-        key_pair.sign(&key_pair.public).compress()
+        key_pair.sign(&StakingContract::proof_of_knowledge_message(&key_pair.public.compress())).compress()
+    }
+
+    /// Dry-runs the configured staking operation against a read-only `StakingContract` snapshot,
+    /// without mutating it, so a wallet can surface the precise on-chain failure reason (e.g.
+    /// "this retire will fail") before paying fees to broadcast it. `unpark_validator_key` must be
+    /// supplied when validating an unpark transaction, since the validator being unparked is not
+    /// itself part of the wire data.
+    ///
+    /// Delegates to `StakingTransaction::validate_against`, the same check
+    /// `TransactionBuilder::validate_against` uses, so the two builders can't drift apart on what
+    /// counts as a valid staking transaction.
+    pub fn validate_against(&self, contract: &StakingContract, sender: &Address, value: Coin, fee: Coin, unpark_validator_key: Option<&CompressedPublicKey>) -> Result<(), TransactionBuilderError> {
+        self.staking_data.as_ref().ok_or(TransactionBuilderError::NoRecipient)?
+            .validate_against(contract, sender, value, fee, unpark_validator_key)
+    }
+
+    /// Builds the `Recipient`, optionally checking it against a `StakingContract` snapshot first
+    /// via `validate_against` so an operation that would fail on-chain never leaves the builder.
+    pub fn generate_checked(self, contract: &StakingContract, sender: &Address, value: Coin, fee: Coin, unpark_validator_key: Option<&CompressedPublicKey>) -> Result<Recipient, TransactionBuilderError> {
+        self.validate_against(contract, sender, value, fee, unpark_validator_key)?;
+        self.generate().ok_or(TransactionBuilderError::NoRecipient)
     }
 
     pub fn generate(self) -> Option<Recipient> {
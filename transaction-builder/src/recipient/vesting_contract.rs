@@ -1,8 +1,11 @@
+use beserial::Serialize;
 use keys::Address;
+use nimiq_account::AccountType;
 use primitives::coin::Coin;
+use primitives::networks::NetworkId;
 use transaction::account::vesting_contract::CreationTransactionData as VestingCreationData;
 
-use crate::recipient::Recipient;
+use crate::recipient::{contract_creation_address, Recipient};
 
 pub struct VestingRecipientBuilder {
     contract_creation_data: VestingCreationData,
@@ -54,6 +57,13 @@ impl VestingRecipientBuilder {
         self
     }
 
+    /// Derives the address this contract will occupy once its creation transaction is mined,
+    /// without having to build and mine that transaction first. See
+    /// `Recipient::contract_address`.
+    pub fn contract_address(&self, creator: &Address, sender_type: AccountType, value: Coin, fee: Coin, validity_start_height: u32, network_id: NetworkId) -> Address {
+        contract_creation_address(&self.contract_creation_data.serialize_to_vec(), creator, sender_type, AccountType::Vesting, value, fee, validity_start_height, network_id)
+    }
+
     pub fn generate(self) -> Recipient {
         Recipient::VestingCreation {
             data: self.into()
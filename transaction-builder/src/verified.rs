@@ -0,0 +1,121 @@
+use std::convert::TryFrom;
+
+use beserial::Deserialize;
+use failure::Fail;
+use keys::Address;
+use primitives::networks::NetworkId;
+use transaction::{SignatureProof, Transaction};
+
+/// A transaction as it comes off the wire or out of a `TransactionProofBuilder`, before its
+/// `proof` has been checked against its content. Opaque on its own; the only way to get at the
+/// underlying `Transaction` is to run it through `VerifiedTransaction::try_from`.
+#[derive(Clone, Debug)]
+pub struct UnverifiedTransaction(Transaction);
+
+impl From<Transaction> for UnverifiedTransaction {
+    fn from(transaction: Transaction) -> Self {
+        UnverifiedTransaction(transaction)
+    }
+}
+
+#[derive(Debug, Fail)]
+pub enum TransactionVerificationError {
+    #[fail(display = "The transaction's proof does not deserialize to a valid SignatureProof.")]
+    InvalidProof,
+    #[fail(display = "The transaction's signature does not verify against its content.")]
+    InvalidSignature,
+    #[fail(display = "The transaction is bound to network {:?}, but {:?} was expected.", actual, expected)]
+    NetworkMismatch { expected: NetworkId, actual: NetworkId },
+}
+
+/// A transaction whose `SignatureProof` has already been checked against
+/// `Transaction::serialize_content()`. The recovered signer is cached on construction, so it
+/// isn't recomputed by every module that goes on to consume this transaction.
+#[derive(Clone, Debug)]
+pub struct VerifiedTransaction {
+    transaction: Transaction,
+    signer: Address,
+}
+
+impl VerifiedTransaction {
+    pub fn transaction(&self) -> &Transaction {
+        &self.transaction
+    }
+
+    pub fn signer(&self) -> &Address {
+        &self.signer
+    }
+
+    pub fn into_transaction(self) -> Transaction {
+        self.transaction
+    }
+
+    /// Verifies many transactions' proofs together as a single Ed25519 batch
+    /// (`SignatureProof::verify_batch`) rather than one at a time, the way a block full of
+    /// staking self-transactions and validator operations arrives at once. Multisig/MuSig
+    /// proofs can't be folded into the batch equation, so they're always verified individually;
+    /// if the single-key batch as a whole fails to verify, every single-key proof in it falls
+    /// back to individual verification too, so only the actual offending transaction(s) are
+    /// rejected instead of the whole batch.
+    pub fn try_from_batch(unverified: Vec<UnverifiedTransaction>) -> Vec<Result<VerifiedTransaction, TransactionVerificationError>> {
+        let parsed: Vec<Result<SignatureProof, _>> = unverified.iter()
+            .map(|tx| Deserialize::deserialize(&mut &tx.0.proof[..]))
+            .collect();
+
+        let batch: Vec<(&SignatureProof, Vec<u8>)> = unverified.iter().zip(&parsed)
+            .filter_map(|(tx, proof)| match proof {
+                Ok(proof) if proof.is_single_key() => Some((proof, tx.0.serialize_content())),
+                _ => None,
+            })
+            .collect();
+        let batch_refs: Vec<(&SignatureProof, &[u8])> = batch.iter()
+            .map(|(proof, message)| (*proof, message.as_slice()))
+            .collect();
+        let batch_ok = batch_refs.is_empty() || SignatureProof::verify_batch(&batch_refs);
+
+        unverified.into_iter().zip(parsed).map(|(unverified, proof)| {
+            match proof {
+                Ok(proof) if proof.is_single_key() && batch_ok => {
+                    let signer = proof.compute_signer();
+                    Ok(VerifiedTransaction { transaction: unverified.0, signer })
+                },
+                _ => VerifiedTransaction::try_from(unverified),
+            }
+        }).collect()
+    }
+
+    /// Like `try_from`, but additionally rejects the transaction if it is not bound to
+    /// `network_id`. A transaction's `network_id` is already part of what it signs
+    /// (`serialize_content`), so the signature itself is inherently network-bound; what this adds
+    /// is comparing that embedded id against a network the caller actually expects, which is only
+    /// meaningful when `network_id` comes from somewhere other than the transaction being checked
+    /// (e.g. the locally configured network, not `unverified.0.network_id` itself).
+    pub fn try_from_for_network(unverified: UnverifiedTransaction, network_id: NetworkId) -> Result<Self, TransactionVerificationError> {
+        if unverified.0.network_id != network_id {
+            return Err(TransactionVerificationError::NetworkMismatch {
+                expected: network_id,
+                actual: unverified.0.network_id,
+            });
+        }
+
+        VerifiedTransaction::try_from(unverified)
+    }
+}
+
+impl TryFrom<UnverifiedTransaction> for VerifiedTransaction {
+    type Error = TransactionVerificationError;
+
+    fn try_from(unverified: UnverifiedTransaction) -> Result<Self, Self::Error> {
+        let transaction = unverified.0;
+
+        let signature_proof: SignatureProof = Deserialize::deserialize(&mut &transaction.proof[..])
+            .map_err(|_| TransactionVerificationError::InvalidProof)?;
+
+        if !signature_proof.verify(&transaction.serialize_content()) {
+            return Err(TransactionVerificationError::InvalidSignature);
+        }
+
+        let signer = signature_proof.compute_signer();
+        Ok(VerifiedTransaction { transaction, signer })
+    }
+}
@@ -9,6 +9,7 @@ use nimiq_primitives::networks::NetworkId;
 use nimiq_transaction::{SignatureProof, Transaction};
 use nimiq_transaction::account::staking_contract::{StakingTransactionData, StakingTransactionType};
 use nimiq_transaction_builder::{TransactionBuilder, Recipient};
+use nimiq_transaction_builder::recipient::staking_contract::StakingRecipientBuilder;
 
 #[test]
 fn it_can_verify_staking_transaction() {
@@ -17,7 +18,7 @@ fn it_can_verify_staking_transaction() {
     let address = Address::from(&key_pair);
     let mut tx = make_incoming_transaction();
 
-    let proof_of_knowledge = bls_pair.sign(&bls_pair.public).compress();
+    let proof_of_knowledge = StakingRecipientBuilder::generate_proof_of_knowledge(&bls_pair);
 
     let data = StakingTransactionData {
         validator_key: bls_pair.public.compress(),
@@ -86,7 +87,7 @@ fn it_can_verify_unstaking_transaction() {
     let mut proof_builder = proof_builder.unwrap_basic();
     proof_builder.sign_with_key_pair(&key_pair);
 
-    assert_eq!(proof_builder.generate().unwrap(), tx);
+    assert_eq!(proof_builder.generate().unwrap().into_transaction(), tx);
 }
 
 #[test]